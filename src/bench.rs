@@ -0,0 +1,33 @@
+//! Helpers for the `#[ignore]`d benchmark tests, run them in release:
+//! `cargo test --release -- --ignored --nocapture`
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Run `f` `iterations` times and print the mean time, returning `f`'s last result
+pub fn time<R, F: FnMut() -> R>(label: &str, iterations: u32, mut f: F) -> R {
+    let start = Instant::now();
+    let mut result = black_box(f());
+    for _ in 1..iterations {
+        result = black_box(f());
+    }
+    let mean = start.elapsed() / iterations;
+    println!("{label}: {}", format_duration(mean));
+    result
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() > 0 {
+        format!("{:.2} s", duration.as_secs_f64())
+    } else {
+        format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
+    }
+}
+
+/// Uniformly scattered points, the same ones every run
+pub fn random_points(count: usize, width: usize, height: usize) -> Vec<(f32, f32)> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..count).map(|_| (rng.gen_range(0.0..width as f32), rng.gen_range(0.0..height as f32))).collect()
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+use crate::spatial::SpatialIndex;
+
+/// What happens to a particle that moves past the edge of its world
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundaryMode {
+    /// Bounce back in, mirroring both position and velocity
+    #[default]
+    Reflect,
+    /// Come back in on the opposite edge. Forces also reach across the edges.
+    Wrap,
+    /// Die on leaving the world
+    Absorb,
+    /// Stop at the edge
+    Clamp,
+}
+
+impl BoundaryMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "reflect" => Some(Self::Reflect),
+            "wrap" => Some(Self::Wrap),
+            "absorb" => Some(Self::Absorb),
+            "clamp" => Some(Self::Clamp),
+            _ => None,
+        }
+    }
+}
+
+/// The edges of a particle world, running from 0 to `width` and 0 to `height`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Bounds {
+    pub width: f32,
+    pub height: f32,
+    pub mode: BoundaryMode,
+}
+
+impl Bounds {
+    /// Bring a particle that has moved past an edge back into the world.
+    /// Returns false if it was absorbed and should die.
+    pub fn apply(&self, x: &mut f32, y: &mut f32, vx: &mut f32, vy: &mut f32) -> bool {
+        Self::apply_axis(self.mode, x, vx, self.width) && Self::apply_axis(self.mode, y, vy, self.height)
+    }
+
+    fn apply_axis(mode: BoundaryMode, position: &mut f32, velocity: &mut f32, size: f32) -> bool {
+        if (0.0..=size).contains(position) && mode != BoundaryMode::Wrap {
+            return true;
+        }
+        match mode {
+            BoundaryMode::Reflect => {
+                *position = if *position < 0.0 { -*position } else { 2.0 * size - *position };
+                *velocity *= -1.0;
+                // Only needed when a particle crosses the whole world in one tick
+                *position = position.clamp(0.0, size);
+            },
+            BoundaryMode::Wrap => {
+                *position = position.rem_euclid(size);
+                // rem_euclid can round up to `size` for tiny negative positions
+                if *position >= size {
+                    *position = 0.0;
+                }
+            },
+            BoundaryMode::Absorb => return false,
+            BoundaryMode::Clamp => {
+                *position = position.clamp(0.0, size);
+                *velocity = 0.0;
+            },
+        }
+        true
+    }
+
+    /// Keep a new position inside the world, wrapping or clamping it
+    pub fn place(&self, x: f32, y: f32) -> (f32, f32) {
+        match self.mode {
+            BoundaryMode::Wrap => ((x.rem_euclid(self.width)).min(self.width.next_down()), (y.rem_euclid(self.height)).min(self.height.next_down())),
+            _ => (x.clamp(0.0, self.width), y.clamp(0.0, self.height)),
+        }
+    }
+
+    /// From (`x2`, `y2`) to (`x1`, `y1`), the shortest way round when wrapping
+    pub fn displacement(&self, x1: f32, y1: f32, x2: f32, y2: f32) -> (f32, f32) {
+        let (dx, dy) = (x1 - x2, y1 - y2);
+        match self.mode {
+            BoundaryMode::Wrap => (dx - self.width * (dx / self.width).round(), dy - self.height * (dy / self.height).round()),
+            _ => (dx, dy),
+        }
+    }
+
+    /// Largest radius forces can reach, when wrapping a particle mustn't see another from both sides
+    pub fn interaction_radius(&self, radius: f32) -> f32 {
+        match self.mode {
+            BoundaryMode::Wrap => radius.min(self.width / 2.0).min(self.height / 2.0),
+            _ => radius,
+        }
+    }
+
+    /// Points to search around for everything within `radius` of (`x`, `y`): the point itself and,
+    /// when wrapping, its copies across any edges the radius reaches over.
+    /// Measuring from whichever copy found a neighbour gives the shortest way round.
+    pub fn images(&self, x: f32, y: f32, radius: f32) -> impl Iterator<Item = (f32, f32)> {
+        let wrap = self.mode == BoundaryMode::Wrap;
+        let shift = |position: f32, size: f32| {
+            if !wrap {
+                0.0
+            } else if position - radius < 0.0 {
+                size
+            } else if position + radius > size {
+                -size
+            } else {
+                0.0
+            }
+        };
+        let (shift_x, shift_y) = (shift(x, self.width), shift(y, self.height));
+        let columns = if shift_x == 0.0 { 1 } else { 2 };
+        let rows = if shift_y == 0.0 { 1 } else { 2 };
+        (0..rows).flat_map(move |row| (0..columns).map(move |column| (x + [0.0, shift_x][column], y + [0.0, shift_y][row])))
+    }
+
+    /// Push (image x, image y, item) for every item within `radius` of (`x`, `y`), measured across
+    /// the edges when wrapping. `radius` must come from `interaction_radius`.
+    pub fn query_radius<T: Copy, I: SpatialIndex<T>>(&self, index: &I, x: f32, y: f32, radius: f32, found: &mut Vec<(f32, f32, T)>, scratch: &mut Vec<T>) {
+        for (image_x, image_y) in self.images(x, y, radius) {
+            scratch.clear();
+            index.query_radius(image_x, image_y, radius, scratch);
+            found.extend(scratch.iter().map(|&item| (image_x, image_y, item)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spatial::spatial_hash::SpatialHash;
+    use crate::spatial::rebuild;
+    use proptest::prelude::*;
+
+    fn mode() -> impl Strategy<Value = BoundaryMode> {
+        prop::sample::select(vec![BoundaryMode::Reflect, BoundaryMode::Wrap, BoundaryMode::Absorb, BoundaryMode::Clamp])
+    }
+
+    proptest! {
+        #[test]
+        fn particles_end_up_inside_the_world(mode in mode(), (x, y) in (-600.0f32..600.0, -600.0f32..600.0), (vx, vy) in (-5.0f32..5.0, -5.0f32..5.0)) {
+            let bounds = Bounds { width: 250.0, height: 160.0, mode };
+            let (mut x, mut y, mut vx, mut vy) = (x, y, vx, vy);
+            let inside = (0.0..=250.0).contains(&x) && (0.0..=160.0).contains(&y);
+            let alive = bounds.apply(&mut x, &mut y, &mut vx, &mut vy);
+            prop_assert_eq!(alive, inside || mode != BoundaryMode::Absorb);
+            if alive {
+                prop_assert!((0.0..=250.0).contains(&x) && (0.0..=160.0).contains(&y), "({x}, {y})");
+                if mode == BoundaryMode::Wrap {
+                    prop_assert!(x < 250.0 && y < 160.0);
+                }
+            }
+        }
+
+        #[test]
+        fn wrapped_queries_find_the_shortest_way_round(
+            points in prop::collection::vec((0.0f32..250.0, 0.0f32..160.0), 0..200),
+            (x, y) in (0.0f32..250.0, 0.0f32..160.0),
+            radius in 0.0f32..200.0,
+        ) {
+            let bounds = Bounds { width: 250.0, height: 160.0, mode: BoundaryMode::Wrap };
+            let radius = bounds.interaction_radius(radius);
+            let mut index = SpatialHash::new(250, 160, radius.max(1.0));
+            rebuild(&mut index, points.iter().copied());
+            let (mut found, mut scratch) = (vec![], vec![]);
+            bounds.query_radius(&index, x, y, radius, &mut found, &mut scratch);
+
+            let wrapped = |d: f32, size: f32| d - size * (d / size).round();
+            let mut expected = vec![];
+            for (i, &(px, py)) in points.iter().enumerate() {
+                let (dx, dy) = (wrapped(x - px, 250.0), wrapped(y - py, 160.0));
+                if dx * dx + dy * dy <= radius * radius {
+                    expected.push(i);
+                }
+            }
+            let mut found_items: Vec<usize> = found.iter().map(|&(_, _, i)| i).collect();
+            found_items.sort_unstable();
+            prop_assert_eq!(found_items, expected);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+/// Shape of the force one particle exerts on another as they get further apart
+pub trait ForceKernel {
+    /// Strength of the push away from a particle `distance` away, for a rule of strength `g`
+    /// reaching `radius`. Negative pulls closer. Only called for distances in (0, `radius`).
+    fn force(&self, distance: f32, radius: f32, g: f32) -> f32;
+}
+
+/// The built in kernels, chosen per rule or for the whole world in `ParticleConfig`
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Kernel {
+    /// The original force: `g` at every distance up to the radius
+    #[default]
+    Constant,
+    /// The usual particle-life shape: every particle pushes away inside the `core` fraction of
+    /// the radius, then `g` rises linearly to its peak halfway through the rest and falls back to 0
+    ParticleLife { core: f32 },
+    /// `g` up to `softening` pixels, falling off with the square of the distance beyond it
+    InverseSquare { softening: f32 },
+    /// Strong push inside `sigma` pixels and a well of depth |`g`| just beyond it.
+    /// Distances below 0.9 `sigma` are treated as 0.9 `sigma` so close particles can't explode apart.
+    LennardJones { sigma: f32 },
+    /// `g` falling off as a bell curve with a standard deviation of `width` pixels
+    Gaussian { width: f32 },
+}
+
+impl Kernel {
+    /// The named kernel with its default parameters
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "constant" => Some(Self::Constant),
+            "particle_life" => Some(Self::ParticleLife { core: 0.3 }),
+            "inverse_square" => Some(Self::InverseSquare { softening: 10.0 }),
+            "lennard_jones" => Some(Self::LennardJones { sigma: 10.0 }),
+            "gaussian" => Some(Self::Gaussian { width: 30.0 }),
+            _ => None,
+        }
+    }
+}
+
+impl ForceKernel for Kernel {
+    fn force(&self, distance: f32, radius: f32, g: f32) -> f32 {
+        match *self {
+            Kernel::Constant => g,
+            Kernel::ParticleLife { core } => {
+                let r = distance / radius;
+                if r < core {
+                    1.0 - r / core
+                } else {
+                    g * (1.0 - (2.0 * r - 1.0 - core).abs() / (1.0 - core))
+                }
+            },
+            Kernel::InverseSquare { softening } => g * (softening / distance.max(softening)).powi(2),
+            Kernel::LennardJones { sigma } => {
+                let s6 = (sigma / distance.max(0.9 * sigma)).powi(6);
+                g.abs() * (2.0 * s6 * s6 - s6)
+            },
+            Kernel::Gaussian { width } => g * (-0.5 * (distance / width).powi(2)).exp(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const KERNELS: [&str; 5] = ["constant", "particle_life", "inverse_square", "lennard_jones", "gaussian"];
+
+    proptest! {
+        #[test]
+        fn forces_are_finite_and_scale_with_g(name in prop::sample::select(KERNELS.to_vec()), distance in 0.001f32..1.0, radius in 1.0f32..500.0, g in -1.0f32..1.0) {
+            let kernel = Kernel::from_name(name).unwrap();
+            let force = kernel.force(distance * radius, radius, g);
+            prop_assert!(force.is_finite());
+            // Only the particle-life core and Lennard-Jones push away whatever the rule says
+            if !matches!(kernel, Kernel::ParticleLife { .. } | Kernel::LennardJones { .. }) {
+                prop_assert!(force.abs() <= g.abs() && force * g >= 0.0, "{force} for g {g}");
+            }
+        }
+    }
+
+    #[test]
+    fn particle_life_repels_inside_the_core_and_peaks_halfway_through_the_rest() {
+        let kernel = Kernel::ParticleLife { core: 0.3 };
+        assert!(kernel.force(10.0, 100.0, -1.0) > 0.0);
+        assert!((kernel.force(30.0, 100.0, -1.0)).abs() < 1e-6);
+        assert!((kernel.force(65.0, 100.0, -1.0) + 1.0).abs() < 1e-6);
+        assert!(kernel.force(99.999, 100.0, -1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn lennard_jones_is_balanced_at_its_minimum() {
+        let kernel = Kernel::LennardJones { sigma: 10.0 };
+        let minimum = 10.0 * 2f32.powf(1.0 / 6.0);
+        assert!(kernel.force(minimum, 100.0, 1.0).abs() < 1e-5);
+        assert!(kernel.force(minimum - 1.0, 100.0, 1.0) > 0.0);
+        assert!(kernel.force(minimum + 1.0, 100.0, 1.0) < 0.0);
+    }
+}
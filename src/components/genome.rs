@@ -0,0 +1,287 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Most children a particle can have in one litter
+pub const MAX_BIRTH_RATE: i8 = 8;
+/// Range a particle's speed can evolve within
+pub const SPEED_RANGE: (f32, f32) = (0.1, 4.0);
+/// Most a rule weight can evolve to either side of 0
+pub const MAX_RULE_WEIGHT: f32 = 2.0;
+/// Range, in tiles, a particle's sensing can evolve within
+pub const SENSING_RANGE: (f32, f32) = (0.0, 4.0);
+/// Most a resource preference can evolve to either side of 0
+pub const MAX_PREFERENCE: f32 = 1.0;
+/// How far each colour channel moves for a mutation of size 1
+const COLOUR_MUTATION: f32 = 64.0;
+/// Most clusters `GenotypeClusters` keeps apart, later genomes join the nearest
+const MAX_CLUSTERS: usize = 64;
+/// Colours clusters are drawn in, by cluster id
+pub const CLUSTER_COLOURS: [[u8; 4]; 12] = [
+    [230, 25, 75, 255],
+    [60, 180, 75, 255],
+    [255, 225, 25, 255],
+    [0, 130, 200, 255],
+    [245, 130, 48, 255],
+    [145, 30, 180, 255],
+    [70, 240, 240, 255],
+    [240, 50, 230, 255],
+    [210, 245, 60, 255],
+    [250, 190, 212, 255],
+    [0, 128, 128, 255],
+    [255, 255, 255, 255],
+];
+
+/// A particle's heritable traits, see `Particle::genome`
+#[derive(Clone, PartialEq, Debug)]
+pub struct Genome {
+    pub birth_rate: i8,
+    pub speed: f32,
+    pub colour: [u8; 3],
+    /// Weight for the rules moving the particle relative to each group, in group order
+    pub rule_weights: Vec<f32>,
+    /// How many tiles away the particle senses resources
+    pub sensing: f32,
+    /// How much the particle is drawn to each resource, in `RESOURCE_TYPES` order, negative
+    /// to avoid it. Empty for particles that don't sense resources.
+    pub preferences: Vec<f32>,
+}
+
+impl Genome {
+    /// Change each gene with chance `rate`: redrawn from anywhere in its range with chance `reset`,
+    /// otherwise nudged by up to `size` of its range
+    pub fn mutate<R: Rng>(&mut self, rate: f32, size: f32, reset: f32, rng: &mut R) {
+        // Resets only draw from the generator when they can happen
+        let resets = |rng: &mut R| reset > 0.0 && rng.gen::<f32>() < reset;
+        if rng.gen::<f32>() < rate {
+            self.birth_rate = if resets(rng) {
+                rng.gen_range(1..=MAX_BIRTH_RATE)
+            } else {
+                let step = if rng.gen() { 1 } else { -1 };
+                (self.birth_rate + step).clamp(1, MAX_BIRTH_RATE)
+            };
+        }
+        if rng.gen::<f32>() < rate {
+            self.speed = if resets(rng) {
+                rng.gen_range(SPEED_RANGE.0..=SPEED_RANGE.1)
+            } else {
+                (self.speed * (1.0 + rng.gen_range(-size..=size))).clamp(SPEED_RANGE.0, SPEED_RANGE.1)
+            };
+        }
+        if rng.gen::<f32>() < rate {
+            if resets(rng) {
+                self.colour = rng.gen();
+            } else {
+                for channel in self.colour.iter_mut() {
+                    *channel = (*channel as f32 + rng.gen_range(-size..=size) * COLOUR_MUTATION).clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+        for weight in self.rule_weights.iter_mut() {
+            if rng.gen::<f32>() < rate {
+                *weight = if resets(rng) {
+                    rng.gen_range(-MAX_RULE_WEIGHT..=MAX_RULE_WEIGHT)
+                } else {
+                    (*weight + rng.gen_range(-size..=size) * MAX_RULE_WEIGHT).clamp(-MAX_RULE_WEIGHT, MAX_RULE_WEIGHT)
+                };
+            }
+        }
+        // Sensing is no use without preferences, leaving it be keeps the generator where it was
+        if self.preferences.is_empty() {
+            return;
+        }
+        if rng.gen::<f32>() < rate {
+            self.sensing = if resets(rng) {
+                rng.gen_range(SENSING_RANGE.0..=SENSING_RANGE.1)
+            } else {
+                (self.sensing + rng.gen_range(-size..=size) * (SENSING_RANGE.1 - SENSING_RANGE.0)).clamp(SENSING_RANGE.0, SENSING_RANGE.1)
+            };
+        }
+        for preference in self.preferences.iter_mut() {
+            if rng.gen::<f32>() < rate {
+                *preference = if resets(rng) {
+                    rng.gen_range(-MAX_PREFERENCE..=MAX_PREFERENCE)
+                } else {
+                    (*preference + rng.gen_range(-size..=size) * MAX_PREFERENCE).clamp(-MAX_PREFERENCE, MAX_PREFERENCE)
+                };
+            }
+        }
+    }
+
+    /// Uniform crossover: each gene from either parent with equal chance
+    pub fn crossover<R: Rng>(&self, other: &Genome, rng: &mut R) -> Genome {
+        let mut from_self = || rng.gen::<bool>();
+        Genome {
+            birth_rate: if from_self() { self.birth_rate } else { other.birth_rate },
+            speed: if from_self() { self.speed } else { other.speed },
+            colour: if from_self() { self.colour } else { other.colour },
+            rule_weights: self.rule_weights.iter().zip(other.rule_weights.iter()).map(|(&a, &b)| if from_self() { a } else { b }).collect(),
+            sensing: if self.preferences.is_empty() || from_self() { self.sensing } else { other.sensing },
+            // A parent that doesn't sense has no preferences to mix in
+            preferences: if self.preferences.len() == other.preferences.len() {
+                self.preferences.iter().zip(other.preferences.iter()).map(|(&a, &b)| if from_self() { a } else { b }).collect()
+            } else {
+                self.preferences.clone()
+            },
+        }
+    }
+
+    /// How different two genomes are, with each kind of gene scaled by its range so they count equally
+    pub fn distance(&self, other: &Genome) -> f32 {
+        let birth_rate = (self.birth_rate - other.birth_rate).abs() as f32 / MAX_BIRTH_RATE as f32;
+        let speed = (self.speed - other.speed).abs() / (SPEED_RANGE.1 - SPEED_RANGE.0);
+        let colour = self.colour.iter().zip(other.colour.iter()).map(|(&a, &b)| (a as f32 - b as f32).abs()).sum::<f32>() / (3.0 * 255.0);
+        let weights = self.rule_weights.iter().zip(other.rule_weights.iter()).map(|(a, b)| (a - b).abs()).sum::<f32>() / (2.0 * MAX_RULE_WEIGHT * self.rule_weights.len().max(1) as f32);
+        let sensing = (self.sensing - other.sensing).abs() / (SENSING_RANGE.1 - SENSING_RANGE.0);
+        let preferences = self.preferences.iter().zip(other.preferences.iter()).map(|(a, b)| (a - b).abs()).sum::<f32>() / (2.0 * MAX_PREFERENCE * self.preferences.len().max(1) as f32);
+        birth_rate + speed + colour + weights + sensing + preferences
+    }
+}
+
+/// Where a particle came from, empty for particles that weren't born
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct Lineage {
+    /// Ids of the particle's parents, the second only for children bred by crossover
+    pub parents: [Option<u32>; 2],
+    /// Id of its first ancestor that wasn't born
+    pub founder: Option<u32>,
+    /// Generations since the founder
+    pub generation: u32,
+}
+
+impl Lineage {
+    pub fn is_founder(&self) -> bool {
+        self.parents[0].is_none()
+    }
+
+    /// Lineage of a child of the particle with id `parent` and this lineage, and of `mate` if it had one
+    pub fn child(&self, parent: u32, mate: Option<(u32, &Lineage)>) -> Lineage {
+        Lineage {
+            parents: [Some(parent), mate.map(|(id, _)| id)],
+            founder: Some(self.founder.unwrap_or(parent)),
+            generation: self.generation.max(mate.map_or(0, |(_, lineage)| lineage.generation)) + 1,
+        }
+    }
+}
+
+/// Groups similar genomes into clusters, keeping each cluster's id between calls to `assign` so
+/// the colours they're drawn in don't flicker
+#[derive(Clone, Debug, Default)]
+pub struct GenotypeClusters {
+    /// Id and founding genome of each cluster
+    leaders: Vec<(u32, Genome)>,
+    next_id: u32,
+}
+
+impl GenotypeClusters {
+    /// Cluster id of each genome: the nearest cluster within `threshold`, else a new cluster.
+    /// Clusters nothing joined are dropped.
+    pub fn assign(&mut self, genomes: impl Iterator<Item = Genome>, threshold: f32) -> Vec<u32> {
+        let mut members = vec![0; self.leaders.len()];
+        let ids = genomes
+            .map(|genome| {
+                let nearest = self.leaders.iter().map(|(_, leader)| leader.distance(&genome)).enumerate().min_by(|a, b| a.1.total_cmp(&b.1));
+                match nearest {
+                    Some((i, distance)) if distance <= threshold || self.leaders.len() >= MAX_CLUSTERS => {
+                        members[i] += 1;
+                        self.leaders[i].0
+                    },
+                    _ => {
+                        let id = self.next_id;
+                        self.next_id += 1;
+                        self.leaders.push((id, genome));
+                        members.push(1);
+                        id
+                    },
+                }
+            })
+            .collect();
+        let mut kept = members.into_iter();
+        self.leaders.retain(|_| kept.next() != Some(0));
+        ids
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaders.len()
+    }
+
+    pub fn colour(id: u32) -> [u8; 4] {
+        CLUSTER_COLOURS[id as usize % CLUSTER_COLOURS.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn genome(seed: u64) -> Genome {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Genome {
+            birth_rate: rng.gen_range(1..=MAX_BIRTH_RATE),
+            speed: rng.gen_range(SPEED_RANGE.0..SPEED_RANGE.1),
+            colour: rng.gen(),
+            rule_weights: vec![rng.gen_range(-1.0..1.0); 4],
+            sensing: rng.gen_range(SENSING_RANGE.0..SENSING_RANGE.1),
+            preferences: vec![rng.gen_range(-MAX_PREFERENCE..MAX_PREFERENCE); 6],
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn mutations_keep_genes_in_range(seed in any::<u64>(), size in 0.0f32..=1.0, reset in 0.0f32..=1.0, generations in 0usize..200) {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut genome = genome(seed);
+            for _ in 0..generations {
+                genome.mutate(1.0, size, reset, &mut rng);
+            }
+            prop_assert!((1..=MAX_BIRTH_RATE).contains(&genome.birth_rate));
+            prop_assert!((SPEED_RANGE.0..=SPEED_RANGE.1).contains(&genome.speed));
+            prop_assert!(genome.rule_weights.iter().all(|w| w.abs() <= MAX_RULE_WEIGHT));
+            prop_assert!((SENSING_RANGE.0..=SENSING_RANGE.1).contains(&genome.sensing));
+            prop_assert!(genome.preferences.iter().all(|p| p.abs() <= MAX_PREFERENCE));
+        }
+
+        #[test]
+        fn genomes_without_preferences_never_sense(seed in any::<u64>()) {
+            let mut genome = Genome { sensing: 0.0, preferences: vec![], ..genome(seed) };
+            let mut rng = StdRng::seed_from_u64(seed);
+            genome.mutate(1.0, 1.0, 0.5, &mut rng);
+            prop_assert_eq!(genome.sensing, 0.0);
+            // Crossing with a sensing mate doesn't give it half a set of preferences
+            let child = genome.crossover(&Genome { preferences: vec![1.0; 6], ..genome.clone() }, &mut rng);
+            prop_assert!(child.preferences.is_empty() && child.sensing == 0.0);
+        }
+
+        #[test]
+        fn crossover_takes_every_gene_from_a_parent(a in any::<u64>(), b in any::<u64>(), seed in any::<u64>()) {
+            let (a, b) = (genome(a), genome(b));
+            let child = a.crossover(&b, &mut StdRng::seed_from_u64(seed));
+            prop_assert!(child.birth_rate == a.birth_rate || child.birth_rate == b.birth_rate);
+            prop_assert!(child.speed == a.speed || child.speed == b.speed);
+            prop_assert!(child.colour == a.colour || child.colour == b.colour);
+            prop_assert!(child.distance(&a) + child.distance(&b) <= a.distance(&b) + 1e-4);
+        }
+    }
+
+    #[test]
+    fn lineages_follow_the_first_parent_back_to_the_founder() {
+        let founder = Lineage::default();
+        let child = founder.child(7, None);
+        let grandchild = child.child(9, Some((8, &founder)));
+        assert!(founder.is_founder() && !child.is_founder());
+        assert_eq!(grandchild, Lineage { parents: [Some(9), Some(8)], founder: Some(7), generation: 2 });
+    }
+
+    #[test]
+    fn clusters_keep_their_ids_and_drop_when_empty() {
+        let mut clusters = GenotypeClusters::default();
+        let (a, b) = (genome(1), genome(2));
+        assert!(a.distance(&b) > 0.1);
+        assert_eq!(clusters.assign([a.clone(), b.clone(), a.clone()].into_iter(), 0.1), vec![0, 1, 0]);
+        assert_eq!(clusters.assign([b.clone()].into_iter(), 0.1), vec![1]);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters.assign([a, b].into_iter(), 0.1), vec![2, 1]);
+    }
+}
@@ -0,0 +1,75 @@
+use pixels::wgpu::Color;
+
+/// Where a particle living on the tile world can go and what it eats there. Each habitat is
+/// one group of `SimGrid::particles`, in `HABITATS` order.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Habitat {
+    /// Walks anywhere but the ocean, eating food
+    Land,
+    /// Swims in the ocean, eating fish
+    Water,
+}
+
+/// Every habitat, in group order
+pub const HABITATS: [Habitat; 2] = [Habitat::Land, Habitat::Water];
+
+impl Habitat {
+    pub fn colour(&self) -> Color {
+        match self {
+            Habitat::Land => Color { r: 255.0, g: 255.0, b: 255.0, a: 255.0 },
+            Habitat::Water => Color { r: 255.0, g: 255.0, b: 0.0, a: 255.0 },
+        }
+    }
+
+    /// Whether particles of this habitat can enter a tile of `terrain_type`
+    pub fn can_enter(&self, terrain_type: char) -> bool {
+        match self {
+            Habitat::Land => terrain_type != 'o' && terrain_type != '.',
+            Habitat::Water => terrain_type == 'o',
+        }
+    }
+
+    /// Resource type particles of this habitat gain life force from, and leave behind when they die
+    pub fn food(&self) -> char {
+        match self {
+            Habitat::Land => 'f',
+            Habitat::Water => 'p',
+        }
+    }
+
+    /// Whether particles of this habitat gain life force from resources of `resource_type`
+    pub fn eats(&self, resource_type: char) -> bool {
+        resource_type == self.food()
+    }
+
+    /// How fast particles of this habitat move over a tile of `terrain_type`, relative to open
+    /// ground. 0 where they can't go.
+    pub fn speed_on(&self, terrain_type: char) -> f32 {
+        if !self.can_enter(terrain_type) {
+            return 0.0;
+        }
+        match terrain_type {
+            'm' => 0.25,
+            'h' => 0.5,
+            'w' => 0.7,
+            _ => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::tile::TERRAIN_TYPES;
+
+    #[test]
+    fn habitats_split_the_terrain_between_them() {
+        for terrain in TERRAIN_TYPES {
+            let entered: Vec<bool> = HABITATS.iter().map(|habitat| habitat.can_enter(terrain)).collect();
+            assert_eq!(entered.iter().filter(|&&entered| entered).count(), 1, "terrain {terrain}");
+            assert!(HABITATS.iter().all(|habitat| (habitat.speed_on(terrain) > 0.0) == habitat.can_enter(terrain)));
+        }
+        assert!(HABITATS.iter().all(|habitat| !habitat.can_enter('.')));
+        assert!(Habitat::Land.speed_on('m') < Habitat::Land.speed_on('p'));
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use log::debug;
+use pixels::wgpu::Color;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::boundary::Bounds;
+use crate::components::genome::{GenotypeClusters, CLUSTER_COLOURS};
+use crate::components::metabolism::FoodTiles;
+use crate::components::particle::Particle;
+use crate::components::particle_config::{parse_at_most, ParticleConfig};
+use crate::components::particle_group::ParticleGroup;
+use crate::components::particle_store::{Bond, ParticleStore, RuleForce, OVERLAP_RADIUS};
+use crate::components::phylogeny::Phylogeny;
+use crate::components::rule::Rule;
+use crate::components::rule_preset::{PresetRule, RulePreset};
+use crate::components::tile::TILE_SIZE;
+use crate::generate_seed;
+use crate::render::accumulation::{heat_colour, AccumulationBuffer, ParticleRendering};
+use crate::render::Canvas;
+use crate::simulation::{loaded_rng, SimRng, Simulation};
+use crate::spatial::barnes_hut::BarnesHutTree;
+use crate::spatial::spatial_hash::SpatialHash;
+use crate::MAX_PARTICLES_PER_GROUP;
+
+/// How different two genomes can be and still be drawn as one genotype, see `Genome::distance`
+const GENOTYPE_THRESHOLD: f32 = 0.15;
+/// Green of a tile full of food, kept dim so particles stand out against it
+const FOOD_BRIGHTNESS: f32 = 60.0;
+/// Shades of each colour the palette holds for blended particles
+const BLEND_STEPS: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
+/// Colours along `heat_colour` the palette holds for heatmaps
+const HEAT_STEPS: usize = 32;
+
+/// The particle-life model: coloured particle groups attracted to or repelled by each other by `rules`
+#[derive(Clone, Debug)]
+pub struct LifeGrid {
+    pub width: usize,
+    pub height: usize,
+    pub global_id_count: u32,
+    pub num_of_particle_groups: usize,
+    pub particles: ParticleStore,
+    pub rules: Vec<Rule>,
+    pub runs_with_life: u32,
+    /// Seed the current particles and rules were generated from
+    pub seed: u64,
+    pub config: ParticleConfig,
+    /// Name of the preset the rules came from, `None` for randomised rules
+    pub preset_name: Option<String>,
+    /// Food for particles to harvest, see `Metabolism`
+    pub food: FoodTiles,
+    /// Draw particles in the colour of their genotype cluster instead of their own
+    pub colour_by_genotype: bool,
+    genotypes: GenotypeClusters,
+    pub rendering: ParticleRendering,
+    /// How much of the last tick's blended particles is kept each tick, leaving trails behind
+    /// moving particles. 0 turns trails off, overwriting never has them.
+    pub trails: f32,
+    /// Blended particles, faded by `trails` rather than cleared between draws
+    accumulation: AccumulationBuffer,
+    /// Ticks since `accumulation` last had the particles added
+    ticks_since_draw: u32,
+    /// Every particle since the current ones were generated or loaded
+    pub phylogeny: Phylogeny,
+    /// Assert the particle store's invariants after every update, only honoured in debug builds
+    pub check_invariants: bool,
+    rng: SimRng,
+    /// Per group indices for force queries, rebuilt every tick
+    force_indices: Vec<SpatialHash<usize>>,
+    /// Per group indices with finer cells for the much shorter overlap queries
+    overlap_indices: Vec<SpatialHash<usize>>,
+    /// Force on each particle slot, reused every tick
+    forces: Vec<(f32, f32)>,
+}
+
+/// What `LifeGrid::save` writes
+#[derive(Serialize, Deserialize)]
+struct LifeGridSave {
+    width: usize,
+    height: usize,
+    global_id_count: u32,
+    num_of_particle_groups: usize,
+    groups: Vec<ParticleGroup>,
+    rules: Vec<Rule>,
+    runs_with_life: u32,
+    seed: u64,
+    #[serde(default)]
+    config: ParticleConfig,
+    #[serde(default)]
+    preset_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    bonds: Vec<SavedBond>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    food: Option<FoodTiles>,
+    /// Only saved once something has been born, generated particles are recorded again on load
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    phylogeny: Option<Phylogeny>,
+    #[serde(default)]
+    rng: Option<SimRng>,
+}
+
+/// A `Bond` between the particles with ids `a` and `b`
+#[derive(Serialize, Deserialize)]
+struct SavedBond {
+    a: u32,
+    b: u32,
+    strength: f32,
+    length: f32,
+    max_length: f32,
+}
+
+impl LifeGrid {
+    pub fn new(width: usize, height: usize, num_of_particle_groups: usize) -> Self {
+        assert!(width != 0 && height != 0);
+        Self {
+            width,
+            height,
+            num_of_particle_groups,
+            particles: ParticleStore::default(),
+            rules: vec![],
+            global_id_count: 1,
+            runs_with_life: 0,
+            seed: 0,
+            config: ParticleConfig::default(),
+            preset_name: None,
+            food: FoodTiles::new(width, height),
+            colour_by_genotype: false,
+            genotypes: GenotypeClusters::default(),
+            rendering: ParticleRendering::default(),
+            trails: 0.0,
+            accumulation: AccumulationBuffer::default(),
+            ticks_since_draw: 0,
+            phylogeny: Phylogeny::default(),
+            check_invariants: false,
+            rng: SimRng::seed_from_u64(0),
+            force_indices: vec![],
+            overlap_indices: vec![],
+            forces: vec![],
+        }
+    }
+
+    fn randomise_rgb_colours(&mut self) -> Vec<Color> {
+        let mut colours: Vec<Color> = vec![];
+
+        for _ in 0..self.num_of_particle_groups {
+            let colour = Color {
+                r: self.rng.gen_range(0..256) as f64,
+                g: self.rng.gen_range(0..256) as f64,
+                b: self.rng.gen_range(0..256) as f64,
+                a: 0xff as f64,
+            };
+            colours.push(colour);
+        }
+        colours
+    }
+
+    /// Replace every particle with new ones spread over the world, one group per colour
+    fn generate_particles(&mut self, colours: Vec<Color>) {
+        self.particles = ParticleStore::new(colours.clone());
+        self.phylogeny = Phylogeny::default();
+        // Trails of the old particles would lead nowhere
+        self.accumulation = AccumulationBuffer::default();
+        for (group, c) in colours.into_iter().enumerate() {
+            let rgba = [c.r as u8, c.g as u8, c.b as u8, c.a as u8];
+            let particles_to_generate = match self.config.particles_per_group {
+                Some(count) => count,
+                None => self.rng.gen_range(0..MAX_PARTICLES_PER_GROUP),
+            };
+            for _ in 0..particles_to_generate {
+                self.global_id_count += 1;
+                let x = self.rng.gen_range(0.0..self.width as f32);
+                let y = self.rng.gen_range(0.0..self.height as f32);
+                self.particles.insert(group, &Particle::new(rgba, self.global_id_count, x, y, 0.0, 0.0, c, 1, &mut self.rng));
+            }
+        }
+    }
+
+    fn randomise_rules(&mut self) {
+        self.rules = vec![];
+        for particle_group_one in 0..self.num_of_particle_groups {
+            for particle_group_two in 0..self.num_of_particle_groups {
+                let colour_one = self.particles.colour(particle_group_one);
+                let colour_two = self.particles.colour(particle_group_two);
+                // Groups can't spawn children with themselves
+                let effect_allowed = particle_group_one != particle_group_two;
+                self.rules.push(Rule::new(colour_one, colour_two, effect_allowed, &mut self.rng));
+            }
+        }
+    }
+
+    fn group_index(&self, colour: Color) -> Option<usize> {
+        self.particles.colours().iter().position(|c| *c == colour)
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds { width: self.width as f32, height: self.height as f32, mode: self.config.boundary }
+    }
+
+    /// Whether food grows or lies anywhere, so is worth feeding particles and drawing
+    fn has_food(&self) -> bool {
+        self.config.metabolism.food_growth > 0.0 || self.food.total() > 0
+    }
+
+    /// Make `indices` hold one index per group, rebuilt from the groups' current positions
+    fn rebuild_indices(particles: &ParticleStore, indices: &mut Vec<SpatialHash<usize>>, width: usize, height: usize, cell_size: f32) {
+        if indices.len() != particles.group_count() || indices.iter().any(|index| index.cell_size() != cell_size) {
+            *indices = vec![SpatialHash::new(width, height, cell_size); particles.group_count()];
+        }
+        indices.par_iter_mut().enumerate().for_each(|(group, index)| particles.rebuild_index(group, index));
+    }
+
+    fn trigger_rules(&mut self) {
+        // Sum the forces from every rule before moving anything, so rule order doesn't matter
+        let bounds = self.bounds();
+        self.forces.clear();
+        self.forces.resize(self.particles.slot_count(), (0.0, 0.0));
+        // Each group's rules, in rule order
+        let mut group_rules: Vec<Vec<RuleForce>> = vec![vec![]; self.particles.group_count()];
+        for r in self.rules.iter() {
+            if let (Some(one), Some(two)) = (self.group_index(r.particle_group_one_colour), self.group_index(r.particle_group_two_colour)) {
+                group_rules[one].push(RuleForce {
+                    other_group: two,
+                    g: r.g,
+                    kernel: r.kernel.unwrap_or(self.config.kernel),
+                    radius: bounds.interaction_radius(r.radius.unwrap_or(self.config.interaction_radius)),
+                });
+            }
+        }
+        match self.config.barnes_hut_theta {
+            Some(theta) => {
+                let tree = BarnesHutTree::build(self.particles.group_positions());
+                for (group, rules) in group_rules.iter().enumerate() {
+                    self.particles.barnes_hut_forces(group, rules, theta, &bounds, &tree, &mut self.forces);
+                }
+            },
+            None => {
+                // Sized so the longest reaching rule only searches the cells around a particle
+                let cell_size = group_rules.iter().flatten().map(|rule| rule.radius).fold(1.0, f32::max);
+                Self::rebuild_indices(&self.particles, &mut self.force_indices, self.width, self.height, cell_size);
+                for (group, rules) in group_rules.iter().enumerate() {
+                    self.particles.rule_forces(group, rules, &bounds, &self.force_indices, &mut self.forces);
+                }
+            },
+        }
+        self.particles.bond_forces(&bounds, &mut self.forces);
+        self.particles.integrate(&self.forces, self.config.time_step, self.config.damping(), &bounds);
+
+        // Apply rule effects where particles now overlap
+        Self::rebuild_indices(&self.particles, &mut self.overlap_indices, self.width, self.height, 4.0 * OVERLAP_RADIUS);
+        for i in 0..self.rules.len() {
+            let r = &self.rules[i];
+            if r.effects.is_empty() {
+                continue;
+            }
+            let (Some(one), Some(two)) = (self.group_index(r.particle_group_one_colour), self.group_index(r.particle_group_two_colour)) else {
+                continue;
+            };
+            let group_lens = (self.particles.members(one).len(), self.particles.members(two).len());
+            for contact in self.particles.contacts(one, &self.overlap_indices[two], &bounds) {
+                for effect in self.rules[i].effects.iter() {
+                    effect.apply(&mut self.particles, one, contact, &bounds, &mut self.global_id_count, &mut self.rng);
+                }
+            }
+            // Spawned and converted particles can overlap with groups in later rules this tick
+            if (self.particles.members(one).len(), self.particles.members(two).len()) != group_lens {
+                self.particles.rebuild_index(one, &mut self.overlap_indices[one]);
+                self.particles.rebuild_index(two, &mut self.overlap_indices[two]);
+            }
+        }
+
+        // Living costs life force, which food gives back
+        let metabolism = &self.config.metabolism;
+        if metabolism.burns() {
+            self.particles.metabolise(metabolism, self.config.time_step);
+        }
+        if metabolism.food_growth > 0.0 {
+            self.food.grow(metabolism.food_growth * self.config.time_step, metabolism.food_capacity);
+        }
+        if self.has_food() {
+            self.food.feed(&mut self.particles, metabolism.harvest, metabolism.food_energy);
+        }
+        self.particles.reproduce(&self.config.reproduction, self.config.time_step, &bounds, &mut self.global_id_count, &mut self.rng);
+        // Before anything dies, so particles that only lived this tick are recorded too
+        self.phylogeny.record_births(&self.particles);
+
+        // Trigger lifecycle events
+        let (food, death_drop, phylogeny) = (&mut self.food, metabolism.death_drop, &mut self.phylogeny);
+        self.particles.lifecycle(metabolism.max_age, |_, id, x, y| {
+            phylogeny.record_death(id);
+            if death_drop > 0 {
+                food.drop_food(x, y, death_drop);
+            }
+        });
+        self.phylogeny.next_tick();
+
+        if self.particles.len() != 0 {
+            self.runs_with_life += 1;
+        }
+    }
+}
+
+impl Simulation for LifeGrid {
+    fn name(&self) -> &'static str {
+        "particles"
+    }
+
+    fn update(&mut self) {
+        self.trigger_rules();
+        self.ticks_since_draw += 1;
+        if cfg!(debug_assertions) && self.check_invariants {
+            self.particles.assert_invariants();
+        }
+    }
+
+    fn draw(&mut self, canvas: &mut dyn Canvas) {
+        // Clear the canvas
+        canvas.clear([0, 0, 0, 255]);
+        if self.has_food() {
+            let capacity = self.config.metabolism.food_capacity.max(1) as f32;
+            for (i, tile) in self.food.tiles.iter().enumerate() {
+                let green = (tile.quantity as f32 / capacity).min(1.0) * FOOD_BRIGHTNESS;
+                if green >= 1.0 {
+                    canvas.fill_tile(i % self.food.columns, i / self.food.columns, TILE_SIZE, [0, green as u8, 0, 255]);
+                }
+            }
+        }
+
+        let particles = &self.particles;
+        let slots: Vec<usize> = (0..particles.group_count()).flat_map(|group| particles.members(group).iter().copied()).collect();
+        let colours: Vec<[u8; 4]> = if self.colour_by_genotype {
+            let clusters = self.genotypes.assign(slots.iter().map(|&slot| particles.genome(slot)), GENOTYPE_THRESHOLD);
+            clusters.into_iter().map(GenotypeClusters::colour).collect()
+        } else {
+            slots.iter().map(|&slot| particles.rgba[slot]).collect()
+        };
+        if self.rendering == ParticleRendering::Overwrite {
+            for (slot, colour) in slots.into_iter().zip(colours) {
+                canvas.blend_pixel(particles.x[slot] as usize, particles.y[slot] as usize, colour, particles.alpha(slot));
+            }
+            return;
+        }
+
+        // Only add the particles once per tick, so redrawing without updating doesn't thicken them
+        let (width, height) = (canvas.width(), canvas.height());
+        if (self.accumulation.width, self.accumulation.height) != (width, height) {
+            self.accumulation = AccumulationBuffer::new(width, height);
+            self.ticks_since_draw = self.ticks_since_draw.max(1);
+        }
+        if self.ticks_since_draw > 0 {
+            self.accumulation.fade(self.trails.powi(self.ticks_since_draw as i32));
+            for (slot, colour) in slots.into_iter().zip(colours) {
+                self.accumulation.add(particles.x[slot] as usize, particles.y[slot] as usize, colour, particles.alpha(slot));
+            }
+            self.ticks_since_draw = 0;
+        }
+        self.accumulation.composite(canvas, self.rendering);
+    }
+
+    fn randomise(&mut self) {
+        self.randomise_with_seed(generate_seed().0);
+    }
+
+    fn randomise_with_seed(&mut self, seed: u64) {
+        debug!("Randomising particle life with seed {}...", seed);
+        self.seed = seed;
+        self.rng = SimRng::seed_from_u64(seed);
+        self.runs_with_life = 0;
+        self.global_id_count = 1;
+        let colours = self.randomise_rgb_colours();
+        self.generate_particles(colours);
+        self.randomise_rules();
+        self.preset_name = None;
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn stats(&self) -> Vec<(&'static str, String)> {
+        let live_groups = (0..self.particles.group_count()).filter(|&group| !self.particles.members(group).is_empty()).count();
+        let mut stats = vec![
+            ("particles", self.particles.len().to_string()),
+            ("groups", format!("{live_groups}/{}", self.particles.group_count())),
+            ("runs with life", self.runs_with_life.to_string()),
+        ];
+        if !self.particles.bonds().is_empty() {
+            stats.push(("bonds", self.particles.bonds().len().to_string()));
+        }
+        if self.has_food() {
+            stats.push(("food", self.food.total().to_string()));
+        }
+        let births = self.phylogeny.births();
+        if births > 0 {
+            stats.push(("births", births.to_string()));
+        }
+        if self.colour_by_genotype {
+            stats.push(("genotypes", self.genotypes.len().to_string()));
+        }
+        stats
+    }
+
+    fn palette(&self) -> Vec<[u8; 4]> {
+        let mut palette = vec![[0, 0, 0, 255], [255, 255, 255, 255]];
+        let colours: Vec<[u8; 4]> = if self.colour_by_genotype {
+            CLUSTER_COLOURS.to_vec()
+        } else {
+            self.particles.colours().iter().map(|c| [c.r as u8, c.g as u8, c.b as u8, 255]).collect()
+        };
+        match self.rendering {
+            // Shades over black, fading trails and weak particles pass through
+            ParticleRendering::Overwrite | ParticleRendering::Blend => palette.extend(BLEND_STEPS.iter().flat_map(|&step| colours.iter().map(move |c| [0, 1, 2].map(|i| (c[i] as f32 * step) as u8)).map(|[r, g, b]| [r, g, b, 255]))),
+            ParticleRendering::Heatmap => palette.extend((1..HEAT_STEPS).map(|i| heat_colour(i as f32 / (HEAT_STEPS - 1) as f32))),
+        }
+        palette.truncate(256);
+        palette
+    }
+
+    fn set_check_invariants(&mut self, check_invariants: bool) {
+        self.check_invariants = check_invariants;
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if name == "colour_by" {
+            self.colour_by_genotype = match value {
+                "group" => false,
+                "genotype" => true,
+                _ => return Err(format!("expected group or genotype, got {value}")),
+            };
+            return Ok(());
+        }
+        if name == "render" {
+            self.rendering = ParticleRendering::from_name(value).ok_or_else(|| format!("expected overwrite, blend or heatmap, got {value}"))?;
+            return Ok(());
+        }
+        if name == "trails" {
+            self.trails = parse_at_most(value, 0.0, 1.0)?;
+            return Ok(());
+        }
+        let particles_per_group = self.config.particles_per_group;
+        self.config.set(name, value)?;
+        if self.config.particles_per_group != particles_per_group {
+            self.generate_particles(self.particles.colours().to_vec());
+        }
+        Ok(())
+    }
+
+    fn rule_preset(&self) -> Option<RulePreset> {
+        let rules = self
+            .rules
+            .iter()
+            .filter_map(|r| {
+                let (one, two) = (self.group_index(r.particle_group_one_colour)?, self.group_index(r.particle_group_two_colour)?);
+                Some(PresetRule { one, two, g: r.g, kernel: r.kernel, radius: r.radius, effects: r.effects.clone() })
+            })
+            .collect();
+        Some(RulePreset {
+            name: self.preset_name.clone().unwrap_or_else(|| format!("seed-{}", self.seed)),
+            groups: self.particles.group_count(),
+            colours: self.particles.colours().iter().map(|c| [c.r as u8, c.g as u8, c.b as u8]).collect(),
+            config: Some(self.config.clone()),
+            rules,
+        })
+    }
+
+    fn phylogeny(&self) -> Option<&Phylogeny> {
+        Some(&self.phylogeny)
+    }
+
+    fn apply_preset(&mut self, preset: &RulePreset) -> Result<(), String> {
+        preset.validate()?;
+        let old_particles_per_group = self.config.particles_per_group;
+        if let Some(config) = &preset.config {
+            self.config = config.clone();
+        }
+        self.num_of_particle_groups = preset.groups;
+        let colours = if !preset.colours.is_empty() {
+            preset.colours.iter().map(|&[r, g, b]| Color { r: r as f64, g: g as f64, b: b as f64, a: 255.0 }).collect()
+        } else if preset.groups == self.particles.group_count() {
+            self.particles.colours().to_vec()
+        } else {
+            self.randomise_rgb_colours()
+        };
+        // Keep the current particles when only the rules change, as when editing them
+        if preset.groups != self.particles.group_count() || self.config.particles_per_group != old_particles_per_group {
+            self.generate_particles(colours);
+        } else {
+            for (group, colour) in colours.into_iter().enumerate() {
+                self.particles.set_colour(group, colour);
+            }
+        }
+        self.rules = preset
+            .rules
+            .iter()
+            .map(|rule| Rule {
+                particle_group_one_colour: self.particles.colour(rule.one),
+                particle_group_two_colour: self.particles.colour(rule.two),
+                g: rule.g,
+                kernel: rule.kernel,
+                radius: rule.radius,
+                effects: rule.effects.clone(),
+            })
+            .collect();
+        self.preset_name = Some(preset.name.clone());
+        Ok(())
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let save = LifeGridSave {
+            width: self.width,
+            height: self.height,
+            global_id_count: self.global_id_count,
+            num_of_particle_groups: self.num_of_particle_groups,
+            groups: (0..self.particles.group_count()).map(|group| ParticleGroup::new(self.particles.colour(group), self.particles.group_particles(group))).collect(),
+            rules: self.rules.clone(),
+            runs_with_life: self.runs_with_life,
+            seed: self.seed,
+            config: self.config.clone(),
+            preset_name: self.preset_name.clone(),
+            bonds: self
+                .particles
+                .bonds()
+                .iter()
+                .filter_map(|bond| {
+                    let (a, b) = (self.particles.get(bond.a)?, self.particles.get(bond.b)?);
+                    Some(SavedBond { a: a.id, b: b.id, strength: bond.strength, length: bond.length, max_length: bond.max_length })
+                })
+                .collect(),
+            food: (self.food.total() > 0).then(|| self.food.clone()),
+            phylogeny: (self.phylogeny.births() > 0).then(|| self.phylogeny.clone()),
+            rng: Some(self.rng.clone()),
+        };
+        serde_json::to_writer(writer, &save)?;
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let save: LifeGridSave = serde_json::from_reader(reader)?;
+        if save.width == 0 || save.height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "saved particle life has no area"));
+        }
+        save.config.metabolism.validate().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("saved metabolism: {err}")))?;
+        save.config.reproduction.validate().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("saved reproduction: {err}")))?;
+        let mut grid = Self::new(save.width, save.height, save.num_of_particle_groups);
+        grid.check_invariants = self.check_invariants;
+        grid.global_id_count = save.global_id_count;
+        grid.particles = ParticleStore::new(save.groups.iter().map(|pg| pg.colour).collect());
+        let mut handles = HashMap::new();
+        for (group, pg) in save.groups.iter().enumerate() {
+            for particle in pg.group.iter() {
+                handles.insert(particle.id, grid.particles.insert(group, particle));
+            }
+        }
+        for bond in save.bonds {
+            let (Some(&a), Some(&b)) = (handles.get(&bond.a), handles.get(&bond.b)) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("saved bond joins missing particles {} and {}", bond.a, bond.b)));
+            };
+            grid.particles.add_bond(Bond { a, b, strength: bond.strength, length: bond.length, max_length: bond.max_length }, u8::MAX);
+        }
+        grid.rules = save.rules;
+        grid.runs_with_life = save.runs_with_life;
+        grid.seed = save.seed;
+        grid.config = save.config;
+        grid.preset_name = save.preset_name;
+        if let Some(food) = save.food {
+            if (food.columns, food.rows, food.tiles.len()) != (grid.food.columns, grid.food.rows, grid.food.tiles.len()) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "saved food doesn't cover the world"));
+            }
+            grid.food = food;
+        }
+        if let Some(phylogeny) = save.phylogeny {
+            grid.phylogeny = phylogeny;
+        }
+        grid.rng = loaded_rng(save.rng, grid.seed, grid.runs_with_life as u64);
+        *self = grid;
+        Ok(())
+    }
+}
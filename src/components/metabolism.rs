@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+
+use crate::components::particle_config::parse_at_least;
+use crate::components::particle_store::ParticleStore;
+use crate::components::resource::Resource;
+use crate::components::tile::TILE_SIZE;
+
+/// How particles of a particle world spend and regain life force. Costs are all 0 by default,
+/// leaving particles immortal unless something else takes their life force.
+/// Set from the command line with `--set name=value`, using the field names.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metabolism {
+    /// Life force a particle burns for every pixel it moves
+    pub movement_cost: f32,
+    /// Life force every particle burns per unit of time
+    pub basal_cost: f32,
+    /// Extra life force burnt per unit of time for every unit of time a particle has lived
+    pub ageing_cost: f32,
+    /// Age particles die of, in units of time. `None` lets them live forever.
+    pub max_age: Option<f32>,
+    /// Food each tile grows per unit of time
+    pub food_growth: f32,
+    /// Most food a tile grows to, though dropped food can pile higher
+    pub food_capacity: i16,
+    /// Most food a particle eats from its tile each tick
+    pub harvest: i16,
+    /// Life force each unit of food gives
+    pub food_energy: i8,
+    /// Food a particle leaves on its tile when it dies
+    pub death_drop: i16,
+}
+
+impl Default for Metabolism {
+    fn default() -> Self {
+        Self {
+            movement_cost: 0.0,
+            basal_cost: 0.0,
+            ageing_cost: 0.0,
+            max_age: None,
+            food_growth: 0.0,
+            food_capacity: 100,
+            harvest: 1,
+            food_energy: 10,
+            death_drop: 0,
+        }
+    }
+}
+
+impl Metabolism {
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "movement_cost" => self.movement_cost = parse_at_least(value, 0.0)?,
+            "basal_cost" => self.basal_cost = parse_at_least(value, 0.0)?,
+            "ageing_cost" => self.ageing_cost = parse_at_least(value, 0.0)?,
+            "max_age" => self.max_age = if value == "off" { None } else { Some(parse_at_least(value, 0.0)?) },
+            "food_growth" => self.food_growth = parse_at_least(value, 0.0)?,
+            "food_capacity" => self.food_capacity = parse_count(value)?,
+            "harvest" => self.harvest = parse_count(value)?,
+            "food_energy" => self.food_energy = value.parse().ok().filter(|&energy: &i8| energy > 0).ok_or_else(|| format!("expected a number from 1 to {}, got {value}", i8::MAX))?,
+            "death_drop" => self.death_drop = parse_count(value)?,
+            _ => return Err(format!("unknown option {name}")),
+        }
+        Ok(())
+    }
+
+    /// Check the limits `set` keeps to, for settings that didn't come through it like loaded ones
+    pub fn validate(&self) -> Result<(), String> {
+        let amounts = [("movement_cost", self.movement_cost), ("basal_cost", self.basal_cost), ("ageing_cost", self.ageing_cost), ("food_growth", self.food_growth)];
+        for (name, value) in amounts.into_iter().chain(self.max_age.map(|max_age| ("max_age", max_age))) {
+            if value.is_nan() || value < 0.0 {
+                return Err(format!("{name} must be at least 0, got {value}"));
+            }
+        }
+        for (name, count) in [("food_capacity", self.food_capacity), ("harvest", self.harvest), ("death_drop", self.death_drop)] {
+            if count < 0 {
+                return Err(format!("{name} must be at least 0, got {count}"));
+            }
+        }
+        if self.food_energy <= 0 {
+            return Err(format!("food_energy must be from 1 to {}, got {}", i8::MAX, self.food_energy));
+        }
+        Ok(())
+    }
+
+    /// Whether particles age and burn life force at all
+    pub fn burns(&self) -> bool {
+        self.movement_cost > 0.0 || self.basal_cost > 0.0 || self.ageing_cost > 0.0 || self.max_age.is_some()
+    }
+
+    /// Life force a particle of `age` moving at `speed` burns over `time_step`
+    pub fn cost(&self, speed: f32, age: f32, time_step: f32) -> f32 {
+        (self.movement_cost * speed + self.basal_cost + self.ageing_cost * age) * time_step
+    }
+}
+
+fn parse_count(value: &str) -> Result<i16, String> {
+    value.parse().ok().filter(|&count: &i16| count >= 0).ok_or_else(|| format!("expected a number from 0 to {}, got {value}", i16::MAX))
+}
+
+/// Let a particle with `life_force` eat up to `harvest` of `food`, as much as it has room to turn
+/// into life force at `food_energy` a unit. What's eaten is gone from `food`.
+pub fn eat(food: &mut Resource, life_force: &mut i8, harvest: i16, food_energy: i8) {
+    // Food worth nothing isn't eaten, which also keeps the room from being divided by zero
+    if food_energy <= 0 {
+        return;
+    }
+    let room = ((i8::MAX - (*life_force).max(0)) / food_energy) as i16;
+    let eaten = harvest.min(food.quantity).min(room);
+    if eaten > 0 {
+        food.remove_resource(eaten);
+        *life_force += eaten as i8 * food_energy;
+    }
+}
+
+/// Food lying on each `TILE_SIZE` tile of a particle world, for particles to harvest
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct FoodTiles {
+    pub columns: usize,
+    pub rows: usize,
+    /// Row by row
+    pub tiles: Vec<Resource>,
+    /// Growth not yet added to the tiles, they only hold whole units
+    growth: f32,
+}
+
+impl FoodTiles {
+    pub fn new(width: usize, height: usize) -> Self {
+        let (columns, rows) = (width.div_ceil(TILE_SIZE), height.div_ceil(TILE_SIZE));
+        let mut empty = Resource::new('f', [0, 0, 0, 255]);
+        empty.quantity = 0;
+        Self { columns, rows, tiles: vec![empty; columns * rows], growth: 0.0 }
+    }
+
+    /// Index of the tile below position (`x`, `y`), the nearest one for positions outside the world
+    pub fn tile_at(&self, x: f32, y: f32) -> usize {
+        let column = ((x.max(0.0) as usize) / TILE_SIZE).min(self.columns - 1);
+        let row = ((y.max(0.0) as usize) / TILE_SIZE).min(self.rows - 1);
+        row * self.columns + column
+    }
+
+    pub fn total(&self) -> i64 {
+        self.tiles.iter().map(|tile| tile.quantity as i64).sum()
+    }
+
+    /// Grow `amount` food on every tile holding less than `capacity`
+    pub fn grow(&mut self, amount: f32, capacity: i16) {
+        self.growth += amount;
+        let whole = self.growth.floor();
+        if whole < 1.0 {
+            return;
+        }
+        self.growth -= whole;
+        for tile in self.tiles.iter_mut() {
+            if tile.quantity < capacity {
+                tile.add_resource((whole as i16).min(capacity - tile.quantity));
+            }
+        }
+    }
+
+    pub fn drop_food(&mut self, x: f32, y: f32, quantity: i16) {
+        let tile = self.tile_at(x, y);
+        self.tiles[tile].add_resource(quantity);
+    }
+
+    /// Let every particle `eat` from its tile. Groups eat in order, and members in member order.
+    pub fn feed(&mut self, store: &mut ParticleStore, harvest: i16, food_energy: i8) {
+        for group in 0..store.group_count() {
+            for i in 0..store.members(group).len() {
+                let slot = store.members(group)[i];
+                let tile = self.tile_at(store.x[slot], store.y[slot]);
+                eat(&mut self.tiles[tile], &mut store.life_force[slot], harvest, food_energy);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::particle::Particle;
+    use pixels::wgpu::Color;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn feeding_never_overflows_life_force(life_force in -128i8..=127, food in 0i16..1000, harvest in 0i16..100, food_energy in 1i8..=127) {
+            let mut store = ParticleStore::new(vec![Color::BLACK]);
+            let handle = store.insert(0, &Particle { x: 40.0, y: 5.0, life_force, ..Particle::default() });
+            let mut food_tiles = FoodTiles::new(64, 64);
+            food_tiles.drop_food(40.0, 5.0, food);
+            food_tiles.feed(&mut store, harvest, food_energy);
+            let gained = store.get(handle).unwrap().life_force as i64 - life_force as i64;
+            prop_assert!(gained >= 0);
+            prop_assert_eq!(food_tiles.total() * food_energy as i64 + gained, food as i64 * food_energy as i64);
+            prop_assert_eq!(food_tiles.tiles[1].quantity as i64, food as i64 - gained / food_energy as i64);
+        }
+    }
+
+    #[test]
+    fn fractional_growth_adds_up_to_whole_food() {
+        let mut food_tiles = FoodTiles::new(100, 40);
+        assert_eq!((food_tiles.columns, food_tiles.rows), (4, 2));
+        for _ in 0..10 {
+            food_tiles.grow(0.25, 2);
+        }
+        // 2.5 grown, but capped at 2 per tile
+        assert!(food_tiles.tiles.iter().all(|tile| tile.quantity == 2));
+        assert_eq!(food_tiles.tile_at(-5.0, 1000.0), 4);
+    }
+
+    #[test]
+    fn burnt_life_force_is_taken_in_whole_units_until_old_age() {
+        let metabolism = Metabolism { basal_cost: 0.4, movement_cost: 0.1, max_age: Some(10.0), ..Metabolism::default() };
+        let mut store = ParticleStore::new(vec![Color::BLACK]);
+        let still = store.insert(0, &Particle { x: 40.0, y: 5.0, life_force: 50, ..Particle::default() });
+        let moving = store.insert(0, &Particle { x: 40.0, y: 50.0, vx: 6.0, life_force: 50, ..Particle::default() });
+        let mut food_tiles = FoodTiles::new(64, 64);
+        for _ in 0..3 {
+            store.metabolise(&metabolism, 1.0);
+        }
+        let (still, moving) = (store.get(still).unwrap(), store.get(moving).unwrap());
+        assert_eq!((still.life_force, moving.life_force), (49, 47));
+        assert!((still.hunger - 0.2).abs() < 1e-6 && still.age == 3.0);
+
+        for _ in 0..7 {
+            store.metabolise(&metabolism, 1.0);
+        }
+        store.lifecycle(metabolism.max_age, |_, _, x, y| food_tiles.drop_food(x, y, 3));
+        assert_eq!(store.len(), 0);
+        assert_eq!((food_tiles.tiles[1].quantity, food_tiles.tiles[3].quantity), (3, 3));
+    }
+
+    #[test]
+    fn options_parse_and_reject_bad_values() {
+        let mut metabolism = Metabolism::default();
+        assert!(!metabolism.burns());
+        metabolism.set("max_age", "300").unwrap();
+        metabolism.set("food_energy", "5").unwrap();
+        assert!(metabolism.burns());
+        assert_eq!((metabolism.max_age, metabolism.food_energy), (Some(300.0), 5));
+        assert!(metabolism.set("food_energy", "0").is_err());
+        assert!(metabolism.set("harvest", "-1").is_err());
+        assert!(metabolism.set("basal_cost", "-0.5").is_err());
+        metabolism.set("max_age", "off").unwrap();
+        assert!(!metabolism.burns());
+
+        assert_eq!(metabolism.validate(), Ok(()));
+        // Loaded settings skip `set`, and would divide by zero when fed
+        let loaded: Metabolism = serde_json::from_str(r#"{"food_energy": 0}"#).unwrap();
+        assert!(loaded.validate().is_err());
+        assert!(Metabolism { basal_cost: f32::NAN, ..Metabolism::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn food_worth_nothing_is_left_uneaten() {
+        let mut food = Resource { quantity: 5, ..Resource::new('f', [0, 0, 0, 255]) };
+        let mut life_force = 10;
+        eat(&mut food, &mut life_force, 3, 0);
+        assert_eq!((food.quantity, life_force), (5, 10));
+    }
+}
@@ -1,5 +1,21 @@
+pub mod boundary;
+pub mod force_kernel;
+pub mod genome;
+pub mod habitat;
 pub mod simulation_grid;
+pub mod life_grid;
+pub mod metabolism;
 pub mod particle;
+pub mod particle_config;
+pub mod particle_group;
+pub mod particle_store;
+pub mod pheromone;
+pub mod physarum;
+pub mod phylogeny;
+pub mod reproduction;
+pub mod rule;
+pub mod rule_effect;
+pub mod rule_preset;
 pub mod tile;
 pub mod pixel;
 pub mod resource;
\ No newline at end of file
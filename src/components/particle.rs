@@ -1,9 +1,22 @@
 use pixels::wgpu::Color;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::{WIDTH, HEIGHT};
+use crate::components::boundary::Bounds;
+use crate::components::genome::{Genome, Lineage};
+use crate::components::reproduction::Reproduction;
 
-#[derive(Clone, Copy, PartialEq, Debug, Default)]
+/// Lets `#[serde(with = "ColorDef")]` save wgpu colours, which don't implement serde themselves
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Color")]
+pub struct ColorDef {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Particle {
     pub pixel_colour_rgba: [u8; 4],
     pub id: u32,
@@ -11,35 +24,84 @@ pub struct Particle {
     pub y: f32,
     pub vx: f32,
     pub vy: f32,
-    pub colour: Color, // TODO: fade alpha to 0 as life force decreases (when enough particles are on screen)
+    #[serde(with = "ColorDef")]
+    /// Group colour. Particles are drawn faded by their life force, see `ParticleStore::alpha`.
+    pub colour: Color,
     // TODO: Add lifecycle logic for birth/survival/death of particles
     /// represents how many children this particle will spawn
     pub birth_rate: i8,
-    // pub birth_cooldown: i8 // TODO: add cooldown to prevent spawning too many children in a short period of time
+    /// Time until the particle can breed again, see `Reproduction`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub birth_cooldown: f32,
     pub life_force: i8,
-    // FIXME: Need to figure out how to add a node to the particle so it can be used for the quadtree (OR DO DIFFERENTLY (LIKELY USING VISIT IN SOME WAY))
+    /// Units of time lived, only counted while the metabolism burns life force
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub age: f32,
+    /// Life force burnt but not yet taken off `life_force`, which only holds whole units
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hunger: f32,
+    /// Multiplies the forces moving the particle
+    #[serde(default = "normal_speed", skip_serializing_if = "is_normal_speed")]
+    pub speed: f32,
+    /// Multiplies the strength of the rules moving the particle, by the group they move it
+    /// relative to. Empty while they're all 1.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rule_weights: Vec<f32>,
+    /// Tiles away the particle senses resources on the tile world, see `Genome::preferences`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub sensing: f32,
+    /// How much the particle is drawn to each resource, in `RESOURCE_TYPES` order. Empty for
+    /// particles that don't sense resources.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preferences: Vec<f32>,
+    #[serde(default, skip_serializing_if = "Lineage::is_founder")]
+    pub lineage: Lineage,
 }
 
-impl Particle {
-    pub fn empty() -> Self {
+impl Default for Particle {
+    fn default() -> Self {
         Self {
             pixel_colour_rgba: [0, 0, 0, 0],
             id: 0,
             x: 0.0,
-            y : 0.0,
-            vx : 0.0,
-            vy : 0.0,
-            colour: Color::WHITE,
+            y: 0.0,
+            vx: 0.0,
+            vy: 0.0,
+            colour: Color::default(),
             birth_rate: 0,
+            birth_cooldown: 0.0,
             life_force: 0,
+            age: 0.0,
+            hunger: 0.0,
+            speed: normal_speed(),
+            rule_weights: vec![],
+            sensing: 0.0,
+            preferences: vec![],
+            lineage: Lineage::default(),
         }
     }
+}
 
-    pub fn new(rgba: [u8; 4], id: u32, x: f32, y: f32, vx: f32, vy: f32, colour: Color, birth_rate: i8) -> Self {
-        let mut rng = rand::thread_rng();
-        Self { pixel_colour_rgba: rgba, id, x, y, vx, vy, colour, birth_rate, life_force: rng.gen_range(50.0..100.0) as i8 }
+fn is_zero(value: &f32) -> bool {
+    *value == 0.0
+}
+
+fn normal_speed() -> f32 {
+    1.0
+}
+
+fn is_normal_speed(speed: &f32) -> bool {
+    *speed == normal_speed()
+}
+
+impl Particle {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<R: Rng>(rgba: [u8; 4], id: u32, x: f32, y: f32, vx: f32, vy: f32, colour: Color, birth_rate: i8, rng: &mut R) -> Self {
+        let life_force = rng.gen_range(50.0..100.0) as i8;
+        Self { pixel_colour_rgba: rgba, id, x, y, vx, vy, colour, birth_rate, life_force, ..Self::default() }
     }
 
+    #[allow(dead_code)]
     pub fn randomise_pixel_colour(&mut self) {
         let mut rng = rand::thread_rng();
         self.pixel_colour_rgba[0] = rng.gen_range(0..255);
@@ -48,60 +110,83 @@ impl Particle {
         self.pixel_colour_rgba[3] = 0xff;
     }
 
-    pub fn update_particle(&mut self, fx: f32, fy: f32) {
-        let mut rng = rand::thread_rng();
-        self.vx = (self.vx + fx)*0.5;
-        self.vy = (self.vy + fy)*0.5;
-        self.x += self.vx;
-        self.y += self.vy;
-        // the rng.gen_range lines appear to cause something akin to mutation and result in constant complexity
-        if self.x < 0.0 || self.x > WIDTH as f32 {
-            // self.x = rng.gen_range(0.0..WIDTH as f32);
-            self.vx *= -1.0;
-        }
-        if self.y < 0.0 || self.y > HEIGHT as f32 {
-            // self.y = rng.gen_range(0.0..HEIGHT as f32);
-            self.vy *= -1.0;
+    /// Children to place up to `offset` pixels away on each axis, one for each of `birth_rate`
+    /// but no more than `max_children`
+    pub fn spawn_children<R: Rng>(&mut self, offset: f32, max_children: usize, bounds: &Bounds, global_id_count: &mut u32, rng: &mut R) -> Vec<Particle> {
+        let mut children: Vec<Particle> = vec![];
+        // TODO: make offsets a property of the particle (to allow for random variation) 
+        let birth_offset = |rng: &mut R| if offset > 0.0 { rng.gen_range(-offset..offset) } else { 0.0 };
+        for _ in 0..(self.birth_rate.max(0) as usize).min(max_children) {
+            // Drawn for each child, children on the same spot would feel no force between them
+            // and move as one forever
+            let birth_offset_x: f32 = birth_offset(rng);
+            let birth_offset_y: f32 = birth_offset(rng);
+            let (x, y) = bounds.place(self.x + birth_offset_x, self.y + birth_offset_y);
+            *global_id_count += 1;
+            let child = Particle::new(self.pixel_colour_rgba, *global_id_count, x, y, 0.0, 0.0, self.colour, self.birth_rate, rng);
+            children.push(Particle {
+                speed: self.speed,
+                rule_weights: self.rule_weights.clone(),
+                sensing: self.sensing,
+                preferences: self.preferences.clone(),
+                lineage: self.lineage.child(self.id, None),
+                ..child
+            });
         }
+        children // return vector so it can be added to the parents particle group
     }
 
-    pub fn lifecycle(&mut self) -> bool {
-        if self.is_alive() {
-            // self.reduce_life_force(1);
-            return true; // is alive (was active)
-        } else {
-            return false; // is dead (was inactive)
+    /// The particle's heritable traits
+    pub fn genome(&self) -> Genome {
+        let [r, g, b, _] = self.pixel_colour_rgba;
+        Genome {
+            birth_rate: self.birth_rate,
+            speed: self.speed,
+            colour: [r, g, b],
+            rule_weights: self.rule_weights.clone(),
+            sensing: self.sensing,
+            preferences: self.preferences.clone(),
         }
     }
 
-    fn is_alive(&mut self) -> bool {
-        if self.life_force <= 0 {
-            return false;
-        }
-        return true;
+    pub fn set_genome(&mut self, genome: Genome) {
+        let [r, g, b] = genome.colour;
+        let a = self.pixel_colour_rgba[3];
+        self.pixel_colour_rgba = [r, g, b, a];
+        self.colour = Color { r: r as f64, g: g as f64, b: b as f64, a: a as f64 };
+        self.birth_rate = genome.birth_rate;
+        self.speed = genome.speed;
+        self.rule_weights = genome.rule_weights;
+        self.sensing = genome.sensing;
+        self.preferences = genome.preferences;
     }
 
-    // TODO: add rule effect for gaining life force (eg consuming life force of another particle)
-    pub fn add_life_force(&mut self, life_force_to_add: i8) {
-        self.life_force += life_force_to_add;
+    /// A child of this particle and `mate` if it has one, given the life force it cost and placed
+    /// up to `Reproduction::offset` pixels away on each axis. Its genome is mutated, after crossing
+    /// over with the mate's.
+    pub fn offspring<R: Rng>(&self, id: u32, mate: Option<&Particle>, reproduction: &Reproduction, bounds: &Bounds, rng: &mut R) -> Particle {
+        let offset = reproduction.offset;
+        let mut birth_offset = || if offset > 0.0 { rng.gen_range(-offset..offset) } else { 0.0 };
+        let (x, y) = bounds.place(self.x + birth_offset(), self.y + birth_offset());
+        let mut child = Particle {
+            id,
+            x,
+            y,
+            vx: 0.0,
+            vy: 0.0,
+            life_force: reproduction.cost,
+            birth_cooldown: reproduction.cooldown,
+            age: 0.0,
+            hunger: 0.0,
+            lineage: self.lineage.child(self.id, mate.map(|mate| (mate.id, &mate.lineage))),
+            ..self.clone()
+        };
+        let mut genome = match mate {
+            Some(mate) => self.genome().crossover(&mate.genome(), rng),
+            None => self.genome(),
+        };
+        genome.mutate(reproduction.mutation_rate, reproduction.mutation_size, reproduction.mutation_reset, rng);
+        child.set_genome(genome);
+        child
     }
-
-    pub fn reduce_life_force(&mut self, life_force_to_reduce: i8) {
-        self.life_force -= life_force_to_reduce;
-    }
-
-    // pub fn spawn_children(&mut self, mut global_id_count: u32) -> Vec<Particle> {
-    //     println!("new particle spawned");
-    //     let mut rng = rand::thread_rng();
-    //     let mut children: Vec<Particle> = vec![];
-    //     // TODO: make offsets a property of the particle (to allow for random variation) 
-    //     let birth_offset_x: f32 = rng.gen_range(-100.0..100.0 as f32);
-    //     let birth_offset_y: f32 = rng.gen_range(-100.0..100.0 as f32);
-    //     global_id_count += 1;
-    //     for _ in 0..self.birth_rate {
-    //         children.push(Particle::new(global_id_count, self.x + birth_offset_x, self.y + birth_offset_y, 0.0, 0.0, self.colour, self.birth_rate));
-    //     }
-    //     // self.reduce_life_force(20); // cost of energy to spawn children
-    //     children // return vector so it can be added to the parents particle group
-    // }
-}
\ No newline at end of file
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+use crate::components::boundary::BoundaryMode;
+use crate::components::force_kernel::Kernel;
+use crate::components::metabolism::Metabolism;
+use crate::components::reproduction::Reproduction;
+
+/// Tunable settings of a particle world, saved along with it.
+/// Set from the command line with `--set name=value`, using the field names.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParticleConfig {
+    /// Particles further apart than this don't exert any force on each other,
+    /// unless their rule has its own radius
+    pub interaction_radius: f32,
+    /// Force kernel for rules that don't choose their own
+    pub kernel: Kernel,
+    /// Fraction of its velocity a particle loses per unit of time
+    pub friction: f32,
+    /// Time each tick advances by. Smaller steps are slower but more stable.
+    pub time_step: f32,
+    /// Particles each group starts with, a random number up to `MAX_PARTICLES_PER_GROUP` when not given
+    pub particles_per_group: Option<usize>,
+    /// Treat far away clusters as single bodies using Barnes–Hut with this accuracy,
+    /// lower is more accurate and 0 is exact. `None` sums every pair directly.
+    pub barnes_hut_theta: Option<f32>,
+    /// What happens to particles at the edges of the world
+    pub boundary: BoundaryMode,
+    /// What living costs and how particles eat, options are set by the field names of `Metabolism`
+    pub metabolism: Metabolism,
+    /// How particles breed, options are set by the names in `Reproduction::OPTIONS`
+    pub reproduction: Reproduction,
+}
+
+impl Default for ParticleConfig {
+    fn default() -> Self {
+        Self {
+            interaction_radius: 100.0,
+            kernel: Kernel::Constant,
+            friction: 0.5,
+            time_step: 1.0,
+            particles_per_group: None,
+            barnes_hut_theta: None,
+            boundary: BoundaryMode::Reflect,
+            metabolism: Metabolism::default(),
+            reproduction: Reproduction::default(),
+        }
+    }
+}
+
+impl ParticleConfig {
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "interaction_radius" => self.interaction_radius = parse_at_least(value, f32::MIN_POSITIVE)?,
+            "kernel" => self.kernel = Kernel::from_name(value).ok_or_else(|| format!("expected constant, particle_life, inverse_square, lennard_jones or gaussian, got {value}"))?,
+            "friction" => self.friction = parse_at_most(value, 0.0, 1.0)?,
+            "time_step" => self.time_step = parse_at_most(value, f32::MIN_POSITIVE, 1.0)?,
+            "particles_per_group" => {
+                self.particles_per_group = if value == "random" { None } else { Some(value.parse().map_err(|_| format!("expected a number or random, got {value}"))?) }
+            },
+            "barnes_hut_theta" => self.barnes_hut_theta = if value == "off" { None } else { Some(parse_at_least(value, 0.0)?) },
+            "boundary" => self.boundary = BoundaryMode::from_name(value).ok_or_else(|| format!("expected reflect, wrap, absorb or clamp, got {value}"))?,
+            name if Reproduction::OPTIONS.contains(&name) => self.reproduction.set(name, value)?,
+            _ => self.metabolism.set(name, value)?,
+        }
+        Ok(())
+    }
+
+    /// How much of its velocity a particle keeps over one tick
+    pub fn damping(&self) -> f32 {
+        (1.0 - self.friction).powf(self.time_step)
+    }
+}
+
+pub fn parse_at_most(value: &str, min: f32, max: f32) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(number) if (min..=max).contains(&number) => Ok(number),
+        _ => Err(format!("expected a number from {min} to {max}, got {value}")),
+    }
+}
+
+pub fn parse_at_least(value: &str, min: f32) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(number) if number >= min => Ok(number),
+        _ => Err(format!("expected a number of at least {min}, got {value}")),
+    }
+}
@@ -0,0 +1,18 @@
+use pixels::wgpu::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::components::particle::{ColorDef, Particle};
+
+/// A colour group's particles, the way `LifeGrid` saves them. While running they live in a `ParticleStore`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ParticleGroup {
+    #[serde(with = "ColorDef")]
+    pub colour: Color,
+    pub group: Vec<Particle>,
+}
+
+impl ParticleGroup {
+    pub fn new(colour: Color, group: Vec<Particle>) -> Self {
+        Self { colour, group }
+    }
+}
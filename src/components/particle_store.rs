@@ -0,0 +1,767 @@
+use std::collections::HashSet;
+
+use pixels::wgpu::Color;
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::components::boundary::Bounds;
+use crate::components::force_kernel::{ForceKernel, Kernel};
+use crate::components::genome::{Genome, Lineage};
+use crate::components::metabolism::Metabolism;
+use crate::components::particle::Particle;
+use crate::components::reproduction::Reproduction;
+use crate::components::resource::RESOURCE_TYPES;
+use crate::components::rule_effect::Contact;
+use crate::spatial::barnes_hut::BarnesHutTree;
+use crate::spatial::quadtree::{Aabb, Point, Quadtree};
+use crate::spatial::SpatialIndex;
+use crate::MAX_PARTICLES_PER_GROUP;
+
+/// Particles within this distance are checked for overlapping, see `check_for_position_overlap`
+pub const OVERLAP_RADIUS: f32 = 1.0;
+/// Life force a particle is drawn fully opaque at, weaker ones fade out
+const FULL_LIFE_FORCE: f32 = 100.0;
+/// Faintest a living particle is drawn, so dying ones don't vanish early
+const MIN_ALPHA: f32 = 0.2;
+
+/// How one group pushes the members of another, resolved from a `Rule` and the world's config
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RuleForce {
+    pub other_group: usize,
+    pub g: f32,
+    pub kernel: Kernel,
+    /// Already limited by `Bounds::interaction_radius`
+    pub radius: f32,
+}
+
+/// Refers to one particle in a `ParticleStore`. Stays valid while the particle lives, and never
+/// refers to a different particle once it dies, even after its slot is reused.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ParticleHandle {
+    slot: u32,
+    generation: u32,
+}
+
+/// Spring between two particles, see `Effect::Bond`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Bond {
+    pub a: ParticleHandle,
+    pub b: ParticleHandle,
+    pub strength: f32,
+    pub length: f32,
+    pub max_length: f32,
+}
+
+/// Every particle of a particle world, stored as one array per field so the hot loops only
+/// touch the fields they need. Dead particles leave their slot on a free list for the next birth.
+/// Each colour group keeps the slots of its members, in the order they were added.
+#[derive(Clone, Debug, Default)]
+pub struct ParticleStore {
+    pub x: Vec<f32>,
+    pub y: Vec<f32>,
+    pub vx: Vec<f32>,
+    pub vy: Vec<f32>,
+    pub rgba: Vec<[u8; 4]>,
+    pub id: Vec<u32>,
+    pub birth_rate: Vec<i8>,
+    pub life_force: Vec<i8>,
+    pub age: Vec<f32>,
+    pub hunger: Vec<f32>,
+    pub birth_cooldown: Vec<f32>,
+    pub speed: Vec<f32>,
+    /// Each slot's weight for every group, see `Particle::rule_weights`
+    rule_weights: Vec<f32>,
+    pub sensing: Vec<f32>,
+    /// Each slot's `Particle::preferences`, all 0 for particles without any
+    pub preferences: Vec<[f32; RESOURCE_TYPES.len()]>,
+    pub lineage: Vec<Lineage>,
+    /// Group of each slot, `None` for free slots
+    group: Vec<Option<usize>>,
+    generation: Vec<u32>,
+    /// Where each slot sits in its group's member list
+    member_position: Vec<usize>,
+    members: Vec<Vec<usize>>,
+    colours: Vec<Color>,
+    free: Vec<usize>,
+    bonds: Vec<Bond>,
+    /// Bonds each slot has
+    bond_count: Vec<u8>,
+    /// Bonded pairs, lowest slot first
+    bonded: HashSet<(ParticleHandle, ParticleHandle)>,
+}
+
+impl ParticleStore {
+    pub fn new(colours: Vec<Color>) -> Self {
+        Self { members: vec![vec![]; colours.len()], colours, ..Self::default() }
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.colours.len()
+    }
+
+    pub fn colour(&self, group: usize) -> Color {
+        self.colours[group]
+    }
+
+    pub fn colours(&self) -> &[Color] {
+        &self.colours
+    }
+
+    /// Recolour `group` and every one of its members
+    pub fn set_colour(&mut self, group: usize, colour: Color) {
+        self.colours[group] = colour;
+        let rgba = [colour.r as u8, colour.g as u8, colour.b as u8, colour.a as u8];
+        for &slot in self.members[group].iter() {
+            self.rgba[slot] = rgba;
+        }
+    }
+
+    /// Live particles
+    pub fn len(&self) -> usize {
+        self.members.iter().map(|members| members.len()).sum()
+    }
+
+    /// Number of slots, live or free, for sizing per slot buffers
+    pub fn slot_count(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Slots of the live particles in `group`
+    pub fn members(&self, group: usize) -> &[usize] {
+        &self.members[group]
+    }
+
+    pub fn handle(&self, slot: usize) -> ParticleHandle {
+        ParticleHandle { slot: slot as u32, generation: self.generation[slot] }
+    }
+
+    /// Slot of the particle, `None` once it has died
+    pub fn slot(&self, handle: ParticleHandle) -> Option<usize> {
+        let slot = handle.slot as usize;
+        (slot < self.slot_count() && self.generation[slot] == handle.generation && self.group[slot].is_some()).then_some(slot)
+    }
+
+    pub fn insert(&mut self, group: usize, particle: &Particle) -> ParticleHandle {
+        assert!(group < self.group_count());
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.x[slot] = particle.x;
+                self.y[slot] = particle.y;
+                self.vx[slot] = particle.vx;
+                self.vy[slot] = particle.vy;
+                self.rgba[slot] = particle.pixel_colour_rgba;
+                self.id[slot] = particle.id;
+                self.birth_rate[slot] = particle.birth_rate;
+                self.life_force[slot] = particle.life_force;
+                self.age[slot] = particle.age;
+                self.hunger[slot] = particle.hunger;
+                self.birth_cooldown[slot] = particle.birth_cooldown;
+                self.speed[slot] = particle.speed;
+                let groups = self.group_count();
+                self.rule_weights[slot * groups..(slot + 1) * groups].copy_from_slice(&Self::weights_for(particle, groups));
+                self.sensing[slot] = particle.sensing;
+                self.preferences[slot] = Self::preferences_for(particle);
+                self.lineage[slot] = particle.lineage;
+                self.group[slot] = Some(group);
+                self.member_position[slot] = self.members[group].len();
+                self.bond_count[slot] = 0;
+                slot
+            },
+            None => {
+                self.x.push(particle.x);
+                self.y.push(particle.y);
+                self.vx.push(particle.vx);
+                self.vy.push(particle.vy);
+                self.rgba.push(particle.pixel_colour_rgba);
+                self.id.push(particle.id);
+                self.birth_rate.push(particle.birth_rate);
+                self.life_force.push(particle.life_force);
+                self.age.push(particle.age);
+                self.hunger.push(particle.hunger);
+                self.birth_cooldown.push(particle.birth_cooldown);
+                self.speed.push(particle.speed);
+                self.rule_weights.extend(Self::weights_for(particle, self.group_count()));
+                self.sensing.push(particle.sensing);
+                self.preferences.push(Self::preferences_for(particle));
+                self.lineage.push(particle.lineage);
+                self.group.push(Some(group));
+                self.generation.push(0);
+                self.member_position.push(self.members[group].len());
+                self.bond_count.push(0);
+                self.slot_count() - 1
+            },
+        };
+        self.members[group].push(slot);
+        self.handle(slot)
+    }
+
+    /// Copy of the particle, `None` once it has died
+    pub fn get(&self, handle: ParticleHandle) -> Option<Particle> {
+        let slot = self.slot(handle)?;
+        let [r, g, b, a] = self.rgba[slot];
+        Some(Particle {
+            pixel_colour_rgba: self.rgba[slot],
+            id: self.id[slot],
+            x: self.x[slot],
+            y: self.y[slot],
+            vx: self.vx[slot],
+            vy: self.vy[slot],
+            colour: Color { r: r as f64, g: g as f64, b: b as f64, a: a as f64 },
+            birth_rate: self.birth_rate[slot],
+            life_force: self.life_force[slot],
+            age: self.age[slot],
+            hunger: self.hunger[slot],
+            birth_cooldown: self.birth_cooldown[slot],
+            speed: self.speed[slot],
+            rule_weights: if self.rule_weights(slot).iter().all(|&weight| weight == 1.0) { vec![] } else { self.rule_weights(slot).to_vec() },
+            sensing: self.sensing[slot],
+            preferences: self.preferences_of(slot),
+            lineage: self.lineage[slot],
+        })
+    }
+
+    /// The particle's rule weights for `groups` groups, 1 for any it doesn't have
+    fn weights_for(particle: &Particle, groups: usize) -> Vec<f32> {
+        let mut weights = particle.rule_weights.clone();
+        weights.resize(groups, 1.0);
+        weights
+    }
+
+    fn preferences_for(particle: &Particle) -> [f32; RESOURCE_TYPES.len()] {
+        let mut preferences = [0.0; RESOURCE_TYPES.len()];
+        for (preference, &value) in preferences.iter_mut().zip(particle.preferences.iter()) {
+            *preference = value;
+        }
+        preferences
+    }
+
+    /// The slot's `Particle::preferences`, empty if it has none
+    fn preferences_of(&self, slot: usize) -> Vec<f32> {
+        if self.preferences[slot].iter().all(|&preference| preference == 0.0) {
+            vec![]
+        } else {
+            self.preferences[slot].to_vec()
+        }
+    }
+
+    /// Genome of the particle in `slot`, with a rule weight for every group
+    pub fn genome(&self, slot: usize) -> Genome {
+        let [r, g, b, _] = self.rgba[slot];
+        Genome {
+            birth_rate: self.birth_rate[slot],
+            speed: self.speed[slot],
+            colour: [r, g, b],
+            rule_weights: self.rule_weights(slot).to_vec(),
+            sensing: self.sensing[slot],
+            preferences: self.preferences_of(slot),
+        }
+    }
+
+    /// How opaque to draw the particle in `slot`, fading with its life force
+    pub fn alpha(&self, slot: usize) -> f32 {
+        (self.life_force[slot] as f32 / FULL_LIFE_FORCE).clamp(MIN_ALPHA, 1.0)
+    }
+
+    /// The slot's weight for the rules moving it relative to each group, in group order
+    pub fn rule_weights(&self, slot: usize) -> &[f32] {
+        let groups = self.group_count();
+        &self.rule_weights[slot * groups..(slot + 1) * groups]
+    }
+
+    /// Take `slot` out of its group's member list, keeping the order of the rest
+    fn leave_group(&mut self, slot: usize) {
+        let group = self.group[slot].expect("live slots have a group");
+        let members = &mut self.members[group];
+        members.remove(self.member_position[slot]);
+        for &member in members[self.member_position[slot]..].iter() {
+            self.member_position[member] -= 1;
+        }
+    }
+
+    pub fn group_of(&self, handle: ParticleHandle) -> Option<usize> {
+        self.group[self.slot(handle)?]
+    }
+
+    /// Move the particle to the end of `group`, recolouring it
+    pub fn move_to_group(&mut self, handle: ParticleHandle, group: usize) {
+        let Some(slot) = self.slot(handle) else {
+            return;
+        };
+        self.leave_group(slot);
+        self.group[slot] = Some(group);
+        self.member_position[slot] = self.members[group].len();
+        self.members[group].push(slot);
+        let colour = self.colours[group];
+        self.rgba[slot] = [colour.r as u8, colour.g as u8, colour.b as u8, colour.a as u8];
+    }
+
+    pub fn bonds(&self) -> &[Bond] {
+        &self.bonds
+    }
+
+    /// Join two live particles, unless they already are or either has `max_bonds` bonds
+    pub fn add_bond(&mut self, bond: Bond, max_bonds: u8) {
+        let (Some(a), Some(b)) = (self.slot(bond.a), self.slot(bond.b)) else {
+            return;
+        };
+        if a == b || self.bond_count[a] >= max_bonds || self.bond_count[b] >= max_bonds || !self.bonded.insert(Self::bond_pair(&bond)) {
+            return;
+        }
+        self.bond_count[a] += 1;
+        self.bond_count[b] += 1;
+        self.bonds.push(bond);
+    }
+
+    /// Keep the bonds between live particles that `keep` returns true for, in order
+    fn retain_bonds(&mut self, mut keep: impl FnMut(&Self, usize, usize, &Bond) -> bool) {
+        let mut bonds = std::mem::take(&mut self.bonds);
+        bonds.retain(|bond| {
+            if let (Some(a), Some(b)) = (self.slot(bond.a), self.slot(bond.b)) {
+                if keep(self, a, b, bond) {
+                    return true;
+                }
+            }
+            // Dead slots get their count reset when they're reused
+            for handle in [bond.a, bond.b] {
+                if let Some(slot) = self.slot(handle) {
+                    self.bond_count[slot] -= 1;
+                }
+            }
+            self.bonded.remove(&Self::bond_pair(bond));
+            false
+        });
+        self.bonds = bonds;
+    }
+
+    fn bond_pair(bond: &Bond) -> (ParticleHandle, ParticleHandle) {
+        if bond.a.slot < bond.b.slot {
+            (bond.a, bond.b)
+        } else {
+            (bond.b, bond.a)
+        }
+    }
+
+    /// Break the bonds stretched past their `max_length`, then add each remaining bond's spring
+    /// force to its particles' slots in `forces`
+    pub fn bond_forces(&mut self, bounds: &Bounds, forces: &mut [(f32, f32)]) {
+        self.retain_bonds(|store, a, b, bond| {
+            let (dx, dy) = bounds.displacement(store.x[a], store.y[a], store.x[b], store.y[b]);
+            dx * dx + dy * dy <= bond.max_length * bond.max_length
+        });
+        for bond in self.bonds.iter() {
+            let (a, b) = (bond.a.slot as usize, bond.b.slot as usize);
+            let (dx, dy) = bounds.displacement(self.x[a], self.y[a], self.x[b], self.y[b]);
+            let d = (dx * dx + dy * dy).sqrt();
+            if d > 0.0 {
+                // Positive when stretched, pulling the ends together
+                let pull = bond.strength * (d - bond.length) / d;
+                forces[a].0 -= pull * dx;
+                forces[a].1 -= pull * dy;
+                forces[b].0 += pull * dx;
+                forces[b].1 += pull * dy;
+            }
+        }
+    }
+
+    /// Remove every particle in `group` that `dead` returns true for, in one pass
+    pub fn remove_where(&mut self, group: usize, mut dead: impl FnMut(&Self, usize) -> bool) {
+        let mut members = std::mem::take(&mut self.members[group]);
+        members.retain(|&slot| {
+            if !dead(self, slot) {
+                return true;
+            }
+            self.group[slot] = None;
+            self.generation[slot] = self.generation[slot].wrapping_add(1);
+            self.free.push(slot);
+            false
+        });
+        for (position, &slot) in members.iter().enumerate() {
+            self.member_position[slot] = position;
+        }
+        self.members[group] = members;
+        if !self.bonds.is_empty() {
+            self.retain_bonds(|_, _, _, _| true);
+        }
+    }
+
+    /// Copies of every live particle in `group`, in member order
+    pub fn group_particles(&self, group: usize) -> Vec<Particle> {
+        self.members[group].iter().filter_map(|&slot| self.get(self.handle(slot))).collect()
+    }
+
+    /// Index the position of each member of `group` by its slot
+    pub fn rebuild_index<I: SpatialIndex<usize>>(&self, group: usize, index: &mut I) {
+        index.clear();
+        for &slot in self.members[group].iter() {
+            index.insert(self.x[slot], self.y[slot], slot);
+        }
+    }
+
+    /// Every group's member positions, in group order
+    pub fn group_positions(&self) -> impl Iterator<Item = impl Iterator<Item = (f32, f32)> + '_> + '_ {
+        self.members.iter().map(|members| members.iter().map(|&slot| (self.x[slot], self.y[slot])))
+    }
+
+    /// Set each member of `group`'s slot in `forces` to the force the groups in `rules` exert on it.
+    /// `indices` must have been rebuilt by `rebuild_index` since the particles last moved.
+    /// Members are summed in parallel, each one in rule order, so the result doesn't depend on the
+    /// number of threads.
+    pub fn rule_forces<I: SpatialIndex<usize> + Sync>(&self, group: usize, rules: &[RuleForce], bounds: &Bounds, indices: &[I], forces: &mut [(f32, f32)]) {
+        let member_forces: Vec<(f32, f32)> = self.members[group]
+            .par_iter()
+            .map_init(|| (Vec::new(), Vec::new()), |(neighbours, scratch), &slot| {
+                let (mut fx, mut fy) = (0.0, 0.0);
+                let weights = self.rule_weights(slot);
+                for rule in rules {
+                    let g = rule.g * weights[rule.other_group];
+                    neighbours.clear();
+                    bounds.query_radius(&indices[rule.other_group], self.x[slot], self.y[slot], rule.radius, neighbours, scratch);
+                    // Measured from the copy of this particle that found the other, so wrapped
+                    // forces pull the shortest way round
+                    for &(x, y, other) in neighbours.iter() {
+                        let dx = x - self.x[other];
+                        let dy = y - self.y[other];
+                        let d = (dx * dx + dy * dy).sqrt();
+                        if d > 0.0 && d < rule.radius {
+                            let force = rule.kernel.force(d, rule.radius, g) / d;
+                            fx += force * dx;
+                            fy += force * dy;
+                        }
+                    }
+                }
+                (fx, fy)
+            })
+            .collect();
+        for (&slot, force) in self.members[group].iter().zip(member_forces) {
+            forces[slot] = force;
+        }
+    }
+
+    /// Like `rule_forces`, approximating distant particles in `tree`
+    pub fn barnes_hut_forces(&self, group: usize, rules: &[RuleForce], theta: f32, bounds: &Bounds, tree: &BarnesHutTree, forces: &mut [(f32, f32)]) {
+        let member_forces: Vec<(f32, f32)> = self.members[group]
+            .par_iter()
+            .map(|&slot| {
+                let (mut fx, mut fy) = (0.0, 0.0);
+                let weights = self.rule_weights(slot);
+                for rule in rules {
+                    let g = rule.g * weights[rule.other_group];
+                    for (x, y) in bounds.images(self.x[slot], self.y[slot], rule.radius) {
+                        let (rule_fx, rule_fy) = tree.rule_force(x, y, rule.other_group, &rule.kernel, g, rule.radius, theta);
+                        fx += rule_fx;
+                        fy += rule_fy;
+                    }
+                }
+                (fx, fy)
+            })
+            .collect();
+        for (&slot, force) in self.members[group].iter().zip(member_forces) {
+            forces[slot] = force;
+        }
+    }
+
+    /// Move every particle by its slot's entry in `forces` scaled by its speed over `time_step`, keeping `damping` of
+    /// its velocity, then bring any that left the world back in. Absorbed particles run out of
+    /// life force, and are removed by the next `lifecycle`.
+    pub fn integrate(&mut self, forces: &[(f32, f32)], time_step: f32, damping: f32, bounds: &Bounds) {
+        // Free slots are moved too, it's cheaper than skipping them
+        let positions = self.x.par_iter_mut().zip(self.y.par_iter_mut());
+        let velocities = self.vx.par_iter_mut().zip(self.vy.par_iter_mut());
+        let pushes = forces.par_iter().zip(self.speed.par_iter());
+        positions.zip(velocities).zip(pushes).zip(self.life_force.par_iter_mut()).for_each(|((((x, y), (vx, vy)), ((fx, fy), speed)), life_force)| {
+            *vx = (*vx + fx * speed * time_step) * damping;
+            *vy = (*vy + fy * speed * time_step) * damping;
+            *x += *vx * time_step;
+            *y += *vy * time_step;
+            if !bounds.apply(x, y, vx, vy) {
+                *life_force = 0;
+            }
+        });
+    }
+
+    /// Every member of `group` sharing a pixel with a particle in `other_index`, in member order.
+    /// `other_index` must have been rebuilt by `rebuild_index` since its particles last moved.
+    pub fn contacts<I: SpatialIndex<usize>>(&self, group: usize, other_index: &I, bounds: &Bounds) -> Vec<Contact> {
+        let mut contacts = vec![];
+        let (mut neighbours, mut scratch) = (vec![], vec![]);
+        for &slot in self.members[group].iter() {
+            neighbours.clear();
+            bounds.query_radius(other_index, self.x[slot], self.y[slot], OVERLAP_RADIUS, &mut neighbours, &mut scratch);
+            for &(x, y, other) in neighbours.iter() {
+                // Particles don't overlap themselves
+                if self.id[slot] == self.id[other] {
+                    continue;
+                }
+                let (dx, dy) = (x - self.x[other], y - self.y[other]);
+                if dx.round() == 0.0 && dy.round() == 0.0 {
+                    contacts.push(Contact { particle: self.handle(slot), other: self.handle(other), dx, dy });
+                }
+            }
+        }
+        contacts
+    }
+
+    /// Age every particle by `time_step` and burn the life force `metabolism` costs for it,
+    /// taking it off `life_force` whenever a whole unit has been burnt
+    pub fn metabolise(&mut self, metabolism: &Metabolism, time_step: f32) {
+        let velocities = self.vx.par_iter().zip(self.vy.par_iter());
+        let state = self.age.par_iter_mut().zip(self.hunger.par_iter_mut()).zip(self.life_force.par_iter_mut());
+        velocities.zip(state).for_each(|((vx, vy), ((age, hunger), life_force))| {
+            let speed = (vx * vx + vy * vy).sqrt();
+            *hunger += metabolism.cost(speed, *age, time_step);
+            *age += time_step;
+            let burnt = hunger.floor();
+            if burnt >= 1.0 {
+                *hunger -= burnt;
+                *life_force = life_force.saturating_sub(burnt.min(i8::MAX as f32) as i8);
+            }
+        });
+    }
+
+    /// Let every particle with the life force `reproduction` asks for and no cooldown left breed a
+    /// litter of up to `birth_rate` mutated children, each costing it `Reproduction::cost`.
+    /// Children join the end of their parent's group, and don't breed until their own cooldown ends.
+    pub fn reproduce<R: Rng>(&mut self, reproduction: &Reproduction, time_step: f32, bounds: &Bounds, global_id_count: &mut u32, rng: &mut R) {
+        let Some(min_life_force) = reproduction.min_life_force else {
+            return;
+        };
+        for group in 0..self.group_count() {
+            // Members from before this group's births, which are the only ones mates are picked from
+            let mates = (reproduction.crossover > 0.0).then(|| {
+                let mut tree = Quadtree::new(Aabb::from_rect(0.0, 0.0, bounds.width, bounds.height));
+                self.rebuild_index(group, &mut tree);
+                tree
+            });
+            for i in 0..self.members[group].len() {
+                let slot = self.members[group][i];
+                if self.birth_cooldown[slot] > 0.0 {
+                    self.birth_cooldown[slot] = (self.birth_cooldown[slot] - time_step).max(0.0);
+                    continue;
+                }
+                if self.life_force[slot] < min_life_force {
+                    continue;
+                }
+                let parent = self.particle_with_genome(slot);
+                // Only draws from the generator when crossover is on, so turning it on doesn't
+                // change anything else
+                let crosses = reproduction.crossover > 0.0 && rng.gen::<f32>() < reproduction.crossover;
+                let mate = match &mates {
+                    Some(mates) if crosses => self.nearest_mate(mates, slot, reproduction.mate_radius, bounds).map(|mate| self.particle_with_genome(mate)),
+                    _ => None,
+                };
+                for _ in 0..parent.birth_rate {
+                    if self.members[group].len() >= MAX_PARTICLES_PER_GROUP || self.life_force[slot] <= reproduction.cost {
+                        break;
+                    }
+                    self.life_force[slot] = self.life_force[slot].saturating_sub(reproduction.cost);
+                    *global_id_count += 1;
+                    let child = parent.offspring(*global_id_count, mate.as_ref(), reproduction, bounds, rng);
+                    self.insert(group, &child);
+                }
+                self.birth_cooldown[slot] = reproduction.cooldown;
+            }
+        }
+    }
+
+    /// Copy of the particle in `slot` with a rule weight for every group, as mutations need
+    fn particle_with_genome(&self, slot: usize) -> Particle {
+        let mut particle = self.get(self.handle(slot)).expect("members are live");
+        particle.rule_weights = self.rule_weights(slot).to_vec();
+        particle
+    }
+
+    /// The particle in `mates` nearest the one in `slot`, other than itself, if any are within
+    /// `radius`. `mates` must have been built by `rebuild_index`.
+    fn nearest_mate(&self, mates: &Quadtree<usize>, slot: usize, radius: f32, bounds: &Bounds) -> Option<usize> {
+        let radius = bounds.interaction_radius(radius);
+        bounds
+            .images(self.x[slot], self.y[slot], radius)
+            .filter_map(|(x, y)| {
+                let image = Point::new(x, y);
+                // Two, as the nearest may be the particle itself
+                let other = mates.nearest(image, 2).into_iter().find(|&other| other != slot)?;
+                Some((other, image.distance_squared(Point::new(self.x[other], self.y[other]))))
+            })
+            .filter(|&(_, d2)| d2 <= radius * radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(other, _)| other)
+    }
+
+    /// Remove the members of every group that have run out of life force or reached `max_age`,
+    /// calling `died` with the group, id and position of each
+    pub fn lifecycle(&mut self, max_age: Option<f32>, mut died: impl FnMut(usize, u32, f32, f32)) {
+        let max_age = max_age.unwrap_or(f32::INFINITY);
+        for group in 0..self.group_count() {
+            self.remove_where(group, |store, slot| {
+                let dead = store.life_force[slot] <= 0 || store.age[slot] >= max_age;
+                if dead {
+                    died(group, store.id[slot], store.x[slot], store.y[slot]);
+                }
+                dead
+            });
+        }
+    }
+
+    pub fn assert_invariants(&self) {
+        let slots = self.slot_count();
+        for field_len in [self.y.len(), self.vx.len(), self.vy.len(), self.rgba.len(), self.id.len(), self.birth_rate.len(), self.life_force.len(), self.age.len(), self.hunger.len(), self.birth_cooldown.len(), self.speed.len(), self.sensing.len(), self.preferences.len(), self.lineage.len(), self.group.len(), self.generation.len(), self.member_position.len(), self.bond_count.len()] {
+            assert_eq!(field_len, slots, "every field has a value per slot");
+        }
+        assert_eq!(self.rule_weights.len(), slots * self.group_count(), "every slot has a weight per group");
+        for (group, members) in self.members.iter().enumerate() {
+            for (position, &slot) in members.iter().enumerate() {
+                assert_eq!(self.group[slot], Some(group), "slot {slot} is a member of group {group}");
+                assert_eq!(self.member_position[slot], position);
+            }
+        }
+        for &slot in self.free.iter() {
+            assert_eq!(self.group[slot], None, "free slot {slot} is unused");
+        }
+        let mut bond_count = vec![0; slots];
+        for bond in self.bonds.iter() {
+            let (a, b) = (self.slot(bond.a).expect("bonds join live particles"), self.slot(bond.b).expect("bonds join live particles"));
+            bond_count[a] += 1;
+            bond_count[b] += 1;
+            assert!(self.bonded.contains(&Self::bond_pair(bond)));
+        }
+        assert_eq!(self.bonded.len(), self.bonds.len(), "every bonded pair has one bond");
+        for (slot, &count) in bond_count.iter().enumerate() {
+            if self.group[slot].is_some() {
+                assert_eq!(self.bond_count[slot], count, "slot {slot} counts its bonds");
+            }
+        }
+        assert_eq!(self.len() + self.free.len(), slots, "every slot is live or free");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench;
+    use crate::components::boundary::BoundaryMode;
+    use proptest::prelude::*;
+
+    fn particle(id: u32, x: f32) -> Particle {
+        Particle { id, x, pixel_colour_rgba: [1, 2, 3, 255], colour: Color { r: 1.0, g: 2.0, b: 3.0, a: 255.0 }, life_force: 50, ..Particle::default() }
+    }
+
+    proptest! {
+        #[test]
+        fn behaves_like_a_vec_per_group(ops in prop::collection::vec((0usize..3, any::<bool>(), any::<prop::sample::Index>()), 0..200)) {
+            let mut store = ParticleStore::new(vec![Color::BLACK; 3]);
+            let mut model: Vec<Vec<(ParticleHandle, Particle)>> = vec![vec![]; 3];
+            let mut removed = vec![];
+            for (i, (group, insert, index)) in ops.into_iter().enumerate() {
+                if insert || model[group].is_empty() {
+                    let p = particle(i as u32, i as f32);
+                    model[group].push((store.insert(group, &p), p));
+                } else {
+                    let i = index.index(model[group].len());
+                    let (handle, p) = model[group].remove(i);
+                    prop_assert_eq!(store.get(handle), Some(p));
+                    store.remove_where(group, |store, slot| store.handle(slot) == handle);
+                    removed.push(handle);
+                }
+                store.assert_invariants();
+            }
+            for (group, particles) in model.iter().enumerate() {
+                prop_assert_eq!(store.group_particles(group), particles.iter().map(|(_, p)| p.clone()).collect::<Vec<_>>());
+                for (handle, p) in particles {
+                    prop_assert_eq!(store.get(*handle), Some(p.clone()));
+                }
+            }
+            // Handles to dead particles stay dead, even once their slot is reused
+            for handle in removed {
+                prop_assert_eq!(store.get(handle), None);
+            }
+        }
+    }
+
+    #[test]
+    fn remove_where_keeps_member_order() {
+        let mut store = ParticleStore::new(vec![Color::BLACK]);
+        let handles: Vec<ParticleHandle> = (0..6).map(|i| store.insert(0, &particle(i, 0.0))).collect();
+        store.remove_where(0, |store, slot| store.id[slot] % 2 == 0);
+        store.assert_invariants();
+        assert_eq!(store.group_particles(0).iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 3, 5]);
+        assert_eq!(store.get(handles[0]), None);
+        // Freed slots are reused
+        store.insert(0, &particle(6, 0.0));
+        assert_eq!(store.slot_count(), 6);
+    }
+
+    #[test]
+    fn bonds_pull_break_and_die_with_their_particles() {
+        let bounds = Bounds { width: 100.0, height: 100.0, mode: BoundaryMode::Wrap };
+        let mut store = ParticleStore::new(vec![Color::BLACK]);
+        let handles: Vec<ParticleHandle> = [(98.0, 50.0), (4.0, 50.0), (50.0, 50.0)].iter().enumerate().map(|(i, &(x, y))| store.insert(0, &Particle { x, y, ..particle(i as u32, x) })).collect();
+        let bond = |a, b| Bond { a: handles[a], b: handles[b], strength: 0.5, length: 2.0, max_length: 10.0 };
+        store.add_bond(bond(0, 1), 2);
+        store.add_bond(bond(1, 2), 2);
+        store.assert_invariants();
+
+        // Pulled together across the edge, 6 apart the short way round
+        let mut forces = vec![(0.0, 0.0); 3];
+        store.bond_forces(&bounds, &mut forces);
+        assert_eq!((forces[0].0, forces[1].0), (2.0, -2.0));
+        // Too far apart to stay bonded
+        assert_eq!(store.bonds().len(), 1);
+        store.assert_invariants();
+
+        store.remove_where(0, |store, slot| store.handle(slot) == handles[1]);
+        assert!(store.bonds().is_empty());
+        store.assert_invariants();
+    }
+
+    /// `cargo test --release particle_store_speedup -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn particle_store_speedup() {
+        const GROUPS: usize = 8;
+        const COUNT: usize = 100_000;
+        let points = bench::random_points(COUNT, 1000, 1000);
+        let mut groups: Vec<Vec<Particle>> = vec![vec![]; GROUPS];
+        let mut store = ParticleStore::new(vec![Color::BLACK; GROUPS]);
+        for (i, &(x, y)) in points.iter().enumerate() {
+            let p = Particle { x, y, ..particle(i as u32, x) };
+            store.insert(i % GROUPS, &p);
+            groups[i % GROUPS].push(p);
+        }
+        let forces = vec![(0.5, -0.5); COUNT];
+        let integrate = |p: &mut Particle, (fx, fy): (f32, f32)| {
+            p.vx = (p.vx + fx) * 0.5;
+            p.vy = (p.vy + fy) * 0.5;
+            p.x += p.vx;
+            p.y += p.vy;
+            if p.x < 0.0 || p.x > 1000.0 {
+                p.vx *= -1.0;
+            }
+            if p.y < 0.0 || p.y > 1000.0 {
+                p.vy *= -1.0;
+            }
+        };
+
+        // What the legacy tick did: copy every group before reading it, then move each particle
+        bench::time("Vec<Particle> groups, cloned per tick", 20, || {
+            let copies: Vec<Vec<Particle>> = groups.clone();
+            let mut i = 0;
+            for group in groups.iter_mut() {
+                for p in group.iter_mut() {
+                    integrate(p, forces[i]);
+                    i += 1;
+                }
+            }
+            copies.len()
+        });
+        bench::time("Vec<Particle> groups, in place", 20, || {
+            let mut i = 0;
+            for group in groups.iter_mut() {
+                for p in group.iter_mut() {
+                    integrate(p, forces[i]);
+                    i += 1;
+                }
+            }
+        });
+        bench::time("ParticleStore", 20, || store.integrate(&forces, 1.0, 0.5, &Bounds { width: 1000.0, height: 1000.0, mode: BoundaryMode::Reflect }));
+    }
+}
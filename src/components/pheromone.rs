@@ -0,0 +1,221 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::particle_config::{parse_at_least, parse_at_most};
+use crate::render::Canvas;
+
+/// Colour the overlay tints pixels holding pheromone
+pub const PHEROMONE_COLOUR: [u8; 4] = [255, 0, 255, 255];
+/// Pheromone a pixel needs for the overlay to tint it as much as it ever does
+const OVERLAY_SATURATION: f32 = 10.0;
+/// Most the overlay tints a pixel, so the terrain still shows through
+const MAX_OVERLAY_ALPHA: f32 = 0.8;
+/// Less than this is drawn as nothing
+const OVERLAY_THRESHOLD: f32 = 0.05;
+
+/// How agents lay and follow a `PheromoneField`. Depositing nothing leaves the field off.
+/// Set from the command line with `--set name=value`, using the names in `OPTIONS`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PheromoneConfig {
+    /// Pheromone a well fed agent lays on its pixel each tick
+    pub deposit: f32,
+    /// Share of the difference with each neighbour a pixel evens out each tick, up to 1
+    pub diffusion: f32,
+    /// Share of the pheromone on every pixel that's lost each tick
+    pub evaporation: f32,
+    /// How strongly agents are pulled up the field's gradient
+    pub follow: f32,
+    /// Tint the terrain by how much pheromone lies on it
+    pub overlay: bool,
+}
+
+impl Default for PheromoneConfig {
+    fn default() -> Self {
+        Self { deposit: 0.0, diffusion: 0.2, evaporation: 0.05, follow: 0.5, overlay: true }
+    }
+}
+
+impl PheromoneConfig {
+    pub const OPTIONS: &'static [&'static str] = &["pheromone_deposit", "pheromone_diffusion", "pheromone_evaporation", "pheromone_follow", "pheromone_overlay"];
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "pheromone_deposit" => self.deposit = parse_at_least(value, 0.0)?,
+            "pheromone_diffusion" => self.diffusion = parse_at_most(value, 0.0, 1.0)?,
+            "pheromone_evaporation" => self.evaporation = parse_at_most(value, 0.0, 1.0)?,
+            "pheromone_follow" => self.follow = parse_at_least(value, 0.0)?,
+            "pheromone_overlay" => {
+                self.overlay = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("expected on or off, got {value}")),
+                }
+            },
+            _ => return Err(format!("unknown option {name}")),
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.deposit > 0.0
+    }
+}
+
+/// Pheromone on every pixel of a world, for agents to lay trails in and follow
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PheromoneField {
+    pub width: usize,
+    pub height: usize,
+    /// Row by row
+    values: Vec<f32>,
+    /// Where `diffuse` writes the next values, kept to save reallocating every tick
+    scratch: Vec<f32>,
+}
+
+impl PheromoneField {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, values: vec![0.0; width * height], scratch: vec![0.0; width * height] }
+    }
+
+    /// Field holding `values`, row by row, `None` if there aren't `width` * `height` of them
+    pub fn from_values(width: usize, height: usize, values: Vec<f32>) -> Option<Self> {
+        (values.len() == width * height).then(|| Self { width, height, values, scratch: vec![0.0; width * height] })
+    }
+
+    /// Every pixel's pheromone, row by row
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    fn index(&self, x: f32, y: f32) -> Option<usize> {
+        (x >= 0.0 && y >= 0.0 && (x as usize) < self.width && (y as usize) < self.height).then(|| y as usize * self.width + x as usize)
+    }
+
+    /// Add `amount` to the pixel at (`x`, `y`), positions outside the field are ignored
+    pub fn deposit(&mut self, x: f32, y: f32, amount: f32) {
+        if let Some(i) = self.index(x, y) {
+            self.values[i] += amount;
+        }
+    }
+
+    /// Pheromone on the pixel at (`x`, `y`), 0 outside the field
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        self.index(x, y).map_or(0.0, |i| self.values[i])
+    }
+
+    /// Direction, of length 1 or 0, in which the field rises fastest at (`x`, `y`), comparing
+    /// the pixels `distance` away on either side
+    pub fn gradient(&self, x: f32, y: f32, distance: f32) -> (f32, f32) {
+        let gx = self.sample(x + distance, y) - self.sample(x - distance, y);
+        let gy = self.sample(x, y + distance) - self.sample(x, y - distance);
+        let length = (gx * gx + gy * gy).sqrt();
+        if length > 0.0 {
+            (gx / length, gy / length)
+        } else {
+            (0.0, 0.0)
+        }
+    }
+
+    pub fn total(&self) -> f32 {
+        self.values.iter().sum()
+    }
+
+    /// Spread the field by moving `diffusion` / 8 of the difference between every pair of
+    /// neighbouring pixels, which keeps the total the same, then lose `evaporation` of it.
+    /// Rows are worked out in parallel from the old values.
+    pub fn diffuse(&mut self, diffusion: f32, evaporation: f32) {
+        let (width, height, values) = (self.width, self.height, &self.values);
+        let keep = 1.0 - evaporation;
+        self.scratch.par_chunks_mut(width.max(1)).enumerate().for_each(|(y, row)| {
+            for (x, out) in row.iter_mut().enumerate() {
+                let here = values[y * width + x];
+                let mut flow = 0.0;
+                for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                    for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                        flow += values[ny * width + nx] - here;
+                    }
+                }
+                *out = (here + diffusion / 8.0 * flow) * keep;
+            }
+        });
+        std::mem::swap(&mut self.values, &mut self.scratch);
+    }
+
+    /// Tint every pixel of `canvas` holding pheromone, more for more
+    pub fn draw_overlay(&self, canvas: &mut dyn Canvas) {
+        for (i, &value) in self.values.iter().enumerate() {
+            if value >= OVERLAY_THRESHOLD {
+                canvas.blend_pixel(i % self.width, i / self.width, PHEROMONE_COLOUR, (value / OVERLAY_SATURATION).min(1.0) * MAX_OVERLAY_ALPHA);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::golden::{assert_golden, Tolerance};
+    use crate::render::image_buffer::ImageBuffer;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn diffusion_keeps_the_total_and_evaporation_takes_its_share(
+            deposits in prop::collection::vec((0.0f32..40.0, 0.0f32..30.0, 0.0f32..100.0), 1..50),
+            diffusion in 0.0f32..=1.0,
+            evaporation in 0.0f32..=1.0,
+        ) {
+            let mut field = PheromoneField::new(40, 30);
+            for &(x, y, amount) in deposits.iter() {
+                field.deposit(x, y, amount);
+            }
+            let total = field.total();
+            field.diffuse(diffusion, 0.0);
+            prop_assert!((field.total() - total).abs() <= total * 1e-4 + 1e-3);
+            prop_assert!(field.values.iter().all(|&value| value >= 0.0));
+            let total = field.total();
+            field.diffuse(diffusion, evaporation);
+            prop_assert!((field.total() - total * (1.0 - evaporation)).abs() <= total * 1e-4 + 1e-3);
+        }
+    }
+
+    #[test]
+    fn trails_spread_and_point_back_to_where_they_were_laid() {
+        let mut field = PheromoneField::new(9, 9);
+        field.deposit(4.5, 4.5, 80.0);
+        field.deposit(20.0, 4.0, 80.0); // Outside, ignored
+        field.diffuse(1.0, 0.0);
+        assert_eq!(field.sample(4.0, 4.0), 0.0);
+        assert_eq!(field.sample(5.0, 5.0), 10.0);
+        assert_eq!(field.gradient(6.0, 4.0, 1.0), (-1.0, 0.0));
+        assert_eq!(field.gradient(0.0, 0.0, 1.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn overlay_matches_golden() {
+        let mut image = ImageBuffer::new(48, 24);
+        image.clear([0, 255, 0, 255]);
+        let mut field = PheromoneField::new(48, 24);
+        // A trail getting stronger to the right, then left to spread
+        for x in 0..40 {
+            field.deposit(4.0 + x as f32, 12.0, x as f32 / 2.0);
+        }
+        for _ in 0..6 {
+            field.diffuse(0.5, 0.05);
+        }
+        field.draw_overlay(&mut image);
+        assert_golden("pheromone_overlay", &image, Tolerance::default());
+    }
+
+    #[test]
+    fn options_parse_and_reject_bad_values() {
+        let mut config = PheromoneConfig::default();
+        assert!(!config.enabled());
+        config.set("pheromone_deposit", "2").unwrap();
+        config.set("pheromone_overlay", "off").unwrap();
+        assert!(config.enabled() && !config.overlay);
+        assert!(config.set("pheromone_diffusion", "1.5").is_err());
+        assert!(config.set("pheromone_overlay", "maybe").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::components::particle_store::ParticleStore;
+
+/// A particle in a `Phylogeny`
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Birth {
+    pub id: u32,
+    /// From the particle's `Lineage`, so may name particles from before the phylogeny started
+    pub parents: [Option<u32>; 2],
+    pub group: usize,
+    /// Tick the particle was first seen on
+    pub born: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub died: Option<u64>,
+}
+
+/// Every particle a particle world has had since it was generated or loaded, with who bred it and
+/// when, so the run's ancestry can be exported as a tree
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct Phylogeny {
+    /// In id order, which puts parents before their children
+    nodes: Vec<Birth>,
+    tick: u64,
+}
+
+/// Summary statistics of a `Phylogeny`, see `Phylogeny::summary`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PhylogenySummary {
+    /// Particles that had parents
+    pub births: usize,
+    pub living: usize,
+    /// Roots with a living descendant
+    pub living_lineages: usize,
+    /// Root and ticks from its birth to the last death in its lineage, or now if one lives on
+    pub longest_lived_lineage: Option<(u32, u64)>,
+    /// Particle and how many children it bred, as either parent
+    pub most_prolific_ancestor: Option<(u32, usize)>,
+}
+
+impl Phylogeny {
+    /// Particles that had parents, the rest were generated or loaded
+    pub fn births(&self) -> usize {
+        self.nodes.iter().filter(|node| node.parents[0].is_some()).count()
+    }
+
+    /// Record every particle in `store` newer than the last one recorded. Ids only grow, so
+    /// these are the particles born since the last call.
+    pub fn record_births(&mut self, store: &ParticleStore) {
+        let last = self.nodes.last().map(|node| node.id);
+        let start = self.nodes.len();
+        for group in 0..store.group_count() {
+            for &slot in store.members(group) {
+                if last.is_none_or(|last| store.id[slot] > last) {
+                    self.nodes.push(Birth { id: store.id[slot], parents: store.lineage[slot].parents, group, born: self.tick, died: None });
+                }
+            }
+        }
+        self.nodes[start..].sort_unstable_by_key(|node| node.id);
+    }
+
+    pub fn record_death(&mut self, id: u32) {
+        if let Some(i) = self.position(id) {
+            self.nodes[i].died.get_or_insert(self.tick);
+        }
+    }
+
+    pub fn next_tick(&mut self) {
+        self.tick += 1;
+    }
+
+    fn position(&self, id: u32) -> Option<usize> {
+        self.nodes.binary_search_by_key(&id, |node| node.id).ok()
+    }
+
+    /// Index of each node's first parent, `None` for roots whose parents aren't recorded
+    fn first_parents(&self) -> Vec<Option<usize>> {
+        self.nodes.iter().map(|node| node.parents[0].and_then(|parent| self.position(parent))).collect()
+    }
+
+    /// Whether each node is alive or an ancestor of a living particle through either parent
+    pub fn extant(&self) -> Vec<bool> {
+        let mut extant: Vec<bool> = self.nodes.iter().map(|node| node.died.is_none()).collect();
+        // Children come after their parents, so one backwards pass reaches every ancestor
+        for i in (0..self.nodes.len()).rev() {
+            if extant[i] {
+                for parent in self.nodes[i].parents.iter().flatten() {
+                    if let Some(p) = self.position(*parent) {
+                        extant[p] = true;
+                    }
+                }
+            }
+        }
+        extant
+    }
+
+    fn kept(&self, prune_extinct: bool) -> Vec<bool> {
+        if prune_extinct {
+            self.extant()
+        } else {
+            vec![true; self.nodes.len()]
+        }
+    }
+
+    /// Graphviz digraph with an edge from each parent to its children, dashed for second parents.
+    /// Living particles are filled.
+    pub fn to_dot(&self, prune_extinct: bool) -> String {
+        let kept = self.kept(prune_extinct);
+        let mut dot = String::from("digraph phylogeny {\n    node [shape=box];\n");
+        for (node, _) in self.nodes.iter().zip(kept.iter()).filter(|(_, &kept)| kept) {
+            let died = node.died.map_or(String::new(), |died| died.to_string());
+            let style = if node.died.is_none() { ", style=filled" } else { "" };
+            writeln!(dot, "    {} [label=\"{}\\ngroup {}\\n{}-{}\"{style}];", node.id, node.id, node.group, node.born, died).unwrap();
+        }
+        for (node, _) in self.nodes.iter().zip(kept.iter()).filter(|(_, &kept)| kept) {
+            for (i, parent) in node.parents.iter().enumerate() {
+                let Some(parent) = parent.filter(|&parent| self.position(parent).is_some_and(|p| kept[p])) else {
+                    continue;
+                };
+                let style = if i == 0 { "" } else { " [style=dashed]" };
+                writeln!(dot, "    {parent} -> {}{style};", node.id).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Newick tree following first parents, labelled by id with branch lengths in ticks between
+    /// births. Roots hang off an unnamed root at tick 0 when there are several.
+    pub fn to_newick(&self, prune_extinct: bool) -> String {
+        let kept = self.kept(prune_extinct);
+        let first_parents = self.first_parents();
+        let mut children = vec![vec![]; self.nodes.len()];
+        let mut roots = vec![];
+        for (i, parent) in first_parents.into_iter().enumerate() {
+            if !kept[i] {
+                continue;
+            }
+            match parent {
+                Some(parent) => children[parent].push(i),
+                None => roots.push(i),
+            }
+        }
+        let mut newick = String::new();
+        if let [root] = roots[..] {
+            self.write_newick(root, None, &children, &mut newick);
+        } else {
+            newick.push('(');
+            for (n, &root) in roots.iter().enumerate() {
+                if n > 0 {
+                    newick.push(',');
+                }
+                self.write_newick(root, Some(0), &children, &mut newick);
+            }
+            newick.push(')');
+        }
+        newick.push_str(";\n");
+        newick
+    }
+
+    fn write_newick(&self, i: usize, parent_born: Option<u64>, children: &[Vec<usize>], newick: &mut String) {
+        let node = &self.nodes[i];
+        if !children[i].is_empty() {
+            newick.push('(');
+            for (n, &child) in children[i].iter().enumerate() {
+                if n > 0 {
+                    newick.push(',');
+                }
+                self.write_newick(child, Some(node.born), children, newick);
+            }
+            newick.push(')');
+        }
+        write!(newick, "{}", node.id).unwrap();
+        if let Some(parent_born) = parent_born {
+            write!(newick, ":{}", node.born - parent_born).unwrap();
+        }
+    }
+
+    /// Lineages follow first parents back to a root, ties go to the lowest id
+    pub fn summary(&self) -> PhylogenySummary {
+        let first_parents = self.first_parents();
+        let extant = self.extant();
+        let mut roots = vec![0; self.nodes.len()];
+        // Last tick each root's lineage was alive on
+        let mut lineage_end = vec![0; self.nodes.len()];
+        let mut children = vec![0; self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            roots[i] = first_parents[i].map_or(i, |parent| roots[parent]);
+            let end = node.died.unwrap_or(self.tick);
+            lineage_end[roots[i]] = lineage_end[roots[i]].max(end);
+            for parent in node.parents.iter().flatten() {
+                if let Some(p) = self.position(*parent) {
+                    children[p] += 1;
+                }
+            }
+        }
+        let is_root = |i: &usize| first_parents[*i].is_none();
+        let longest_lived_lineage = (0..self.nodes.len()).filter(is_root).map(|i| (self.nodes[i].id, lineage_end[i] - self.nodes[i].born)).max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
+        let most_prolific_ancestor = (0..self.nodes.len()).filter(|&i| children[i] > 0).map(|i| (self.nodes[i].id, children[i])).max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
+        PhylogenySummary {
+            births: self.births(),
+            living: self.nodes.iter().filter(|node| node.died.is_none()).count(),
+            living_lineages: (0..self.nodes.len()).filter(is_root).filter(|&i| extant[i]).count(),
+            longest_lived_lineage,
+            most_prolific_ancestor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::genome::Lineage;
+    use crate::components::particle::Particle;
+    use pixels::wgpu::Color;
+
+    /// Founders 1 and 2, 1 breeds 3 then 3 and 2 breed 4. 1, 2 and 3 die, leaving 4, and founder 5
+    /// dies without children.
+    fn family() -> Phylogeny {
+        let mut store = ParticleStore::new(vec![Color::BLACK, Color::WHITE]);
+        let mut phylogeny = Phylogeny::default();
+        let founder = |id| Particle { id, ..Particle::default() };
+        let one = store.insert(0, &founder(1));
+        let two = store.insert(1, &founder(2));
+        store.insert(1, &founder(5));
+        phylogeny.record_births(&store);
+        phylogeny.next_tick();
+        // Children always get ids above every particle before them
+        let three = store.insert(0, &Particle { id: 6, lineage: Lineage::default().child(1, None), ..Particle::default() });
+        phylogeny.record_births(&store);
+        phylogeny.record_death(5);
+        for _ in 0..4 {
+            phylogeny.next_tick();
+        }
+        store.insert(0, &Particle { id: 7, lineage: Lineage::default().child(6, Some((2, &Lineage::default()))), ..Particle::default() });
+        phylogeny.record_births(&store);
+        phylogeny.next_tick();
+        for handle in [one, two, three] {
+            phylogeny.record_death(store.get(handle).unwrap().id);
+        }
+        phylogeny.next_tick();
+        phylogeny
+    }
+
+    #[test]
+    fn births_are_recorded_once_with_their_parents_and_tick() {
+        let phylogeny = family();
+        let birth = |id, parents, group, born, died| Birth { id, parents, group, born, died };
+        assert_eq!(
+            phylogeny.nodes,
+            vec![
+                birth(1, [None, None], 0, 0, Some(6)),
+                birth(2, [None, None], 1, 0, Some(6)),
+                birth(5, [None, None], 1, 0, Some(1)),
+                birth(6, [Some(1), None], 0, 1, Some(6)),
+                birth(7, [Some(6), Some(2)], 0, 5, None),
+            ]
+        );
+        assert_eq!(phylogeny.births(), 2);
+    }
+
+    #[test]
+    fn exports_prune_extinct_branches() {
+        let phylogeny = family();
+        assert_eq!(phylogeny.to_newick(false), "(((7:4)6:1)1:0,2:0,5:0);\n");
+        assert_eq!(phylogeny.to_newick(true), "(((7:4)6:1)1:0,2:0);\n");
+        let dot = phylogeny.to_dot(true);
+        assert!(dot.contains("6 -> 7;") && dot.contains("2 -> 7 [style=dashed];") && dot.contains("7 [label=\"7\\ngroup 0\\n5-\", style=filled];"));
+        assert!(!dot.contains("\n    5 "));
+    }
+
+    #[test]
+    fn summary_finds_the_longest_lived_lineage_and_most_prolific_ancestor() {
+        let summary = family().summary();
+        assert_eq!(summary, PhylogenySummary { births: 2, living: 1, living_lineages: 2, longest_lived_lineage: Some((1, 7)), most_prolific_ancestor: Some((1, 1)) });
+        assert_eq!(Phylogeny::default().summary().longest_lived_lineage, None);
+    }
+}
@@ -0,0 +1,433 @@
+use std::f32::consts::TAU;
+use std::io::{self, Read, Write};
+
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::particle_config::{parse_at_least, parse_at_most};
+use crate::components::pheromone::PheromoneField;
+use crate::components::simulation_grid::SimGrid;
+use crate::components::tile::TILE_SIZE;
+use crate::render::accumulation::heat_colour;
+use crate::render::Canvas;
+use crate::simulation::{SimRng, Simulation};
+use crate::generate_seed;
+
+/// Ticks the tiles grow resources for before food sources are placed on them
+const FOOD_GROWTH_TICKS: usize = 20;
+/// Pixels to either side of its centre a food source covers
+const FOOD_RADIUS: usize = 1;
+const FOOD_COLOUR: [u8; 4] = [0, 255, 120, 255];
+/// Trail, in agent deposits, at which a pixel is about two thirds of the way up `heat_colour`
+const TRAIL_SCALE: f32 = 3.0;
+/// Colours along `heat_colour` the palette holds
+const HEAT_STEPS: usize = 32;
+
+/// How Physarum agents sense, turn and lay trail, and how the trail spreads.
+/// Set from the command line with `--set name=value`, using the names in `OPTIONS`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysarumConfig {
+    /// Agents per pixel `randomise` places
+    pub density: f32,
+    /// Degrees to either side of its heading an agent's outer sensors point
+    pub sensor_angle: f32,
+    /// Pixels ahead of an agent its sensors are
+    pub sensor_distance: f32,
+    /// Degrees an agent turns on a tick it turns
+    pub rotation_angle: f32,
+    /// Pixels an agent moves each tick
+    pub step_size: f32,
+    /// Trail each agent lays on its pixel each tick
+    pub deposit: f32,
+    /// Share of the difference with each neighbour a pixel of trail evens out each tick, up to 1
+    pub diffusion: f32,
+    /// Share of the trail on every pixel that's lost each tick
+    pub decay: f32,
+    /// Trail the richest food source lays on each of its pixels each tick
+    pub food_deposit: f32,
+}
+
+impl Default for PhysarumConfig {
+    fn default() -> Self {
+        Self {
+            density: 0.15,
+            sensor_angle: 22.5,
+            sensor_distance: 9.0,
+            rotation_angle: 45.0,
+            step_size: 1.0,
+            deposit: 5.0,
+            diffusion: 0.5,
+            decay: 0.1,
+            food_deposit: 10.0,
+        }
+    }
+}
+
+impl PhysarumConfig {
+    pub const OPTIONS: &'static [&'static str] =
+        &["agent_density", "sensor_angle", "sensor_distance", "rotation_angle", "step_size", "deposit", "diffusion", "decay", "food_deposit"];
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "agent_density" => self.density = parse_at_most(value, 0.0, 10.0)?,
+            "sensor_angle" => self.sensor_angle = parse_at_most(value, 0.0, 180.0)?,
+            "sensor_distance" => self.sensor_distance = parse_at_least(value, 0.0)?,
+            "rotation_angle" => self.rotation_angle = parse_at_most(value, 0.0, 180.0)?,
+            "step_size" => self.step_size = parse_at_least(value, 0.0)?,
+            "deposit" => self.deposit = parse_at_least(value, 0.0)?,
+            "diffusion" => self.diffusion = parse_at_most(value, 0.0, 1.0)?,
+            "decay" => self.decay = parse_at_most(value, 0.0, 1.0)?,
+            "food_deposit" => self.food_deposit = parse_at_least(value, 0.0)?,
+            _ => return Err(format!("unknown option {name}")),
+        }
+        Ok(())
+    }
+}
+
+/// A Physarum agent, which only knows where it is and which way it's facing
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Agent {
+    pub x: f32,
+    pub y: f32,
+    /// Radians from 0 to tau, clockwise from the x axis as y grows down the screen
+    pub heading: f32,
+}
+
+impl Agent {
+    /// Turn towards the strongest trail the sensors find, then step forward. `random` are two
+    /// numbers from 0 to 1, for which way to turn when both sides beat ahead and which way to
+    /// face after bumping into an edge, where the agent stays put.
+    fn step(&mut self, trail: &PheromoneField, config: &PhysarumConfig, random: [f32; 2]) {
+        let sense = |angle: f32| trail.sample(self.x + angle.cos() * config.sensor_distance, self.y + angle.sin() * config.sensor_distance);
+        let sensor_angle = config.sensor_angle.to_radians();
+        let (left, ahead, right) = (sense(self.heading - sensor_angle), sense(self.heading), sense(self.heading + sensor_angle));
+        let turn = config.rotation_angle.to_radians();
+        if ahead >= left && ahead >= right {
+            // Keep going
+        } else if ahead < left && ahead < right {
+            self.heading += if random[0] < 0.5 { -turn } else { turn };
+        } else if left > right {
+            self.heading -= turn;
+        } else {
+            self.heading += turn;
+        }
+        let (x, y) = (self.x + self.heading.cos() * config.step_size, self.y + self.heading.sin() * config.step_size);
+        if x >= 0.0 && y >= 0.0 && x < trail.width as f32 && y < trail.height as f32 {
+            (self.x, self.y) = (x, y);
+        } else {
+            self.heading = random[1] * TAU;
+        }
+        self.heading = self.heading.rem_euclid(TAU);
+    }
+}
+
+/// Where a food source sits and how much of `PhysarumConfig::food_deposit` it lays
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FoodSource {
+    pub x: usize,
+    pub y: usize,
+    /// From 0 to 1, relative to the richest source
+    pub strength: f32,
+}
+
+/// Random number from 0 to 1 for the `n`th draw of `tick`, the same whichever thread works it out
+fn tick_random(seed: u64, tick: u64, n: usize) -> f32 {
+    // SplitMix64's finaliser over the inputs
+    let mut z = seed ^ tick.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (n as u64).wrapping_mul(0xD6E8_FEB8_6659_FD93);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// A food source at the centre of every tile holding resources after the tile world the same
+/// seed gives has grown them for `FOOD_GROWTH_TICKS`, stronger on tiles holding more
+fn place_food(width: usize, height: usize, seed: u64) -> Vec<FoodSource> {
+    let mut tiles = SimGrid::new(width, height, 1);
+    tiles.walkers_per_habitat = 0;
+    tiles.randomise_with_seed(seed);
+    for _ in 0..FOOD_GROWTH_TICKS {
+        tiles.update();
+    }
+    let quantities: Vec<i64> = tiles.tiles.iter().map(|tile| tile.resources.iter().map(|r| r.quantity as i64).sum()).collect();
+    let richest = quantities.iter().copied().max().unwrap_or(0);
+    tiles
+        .tiles
+        .iter()
+        .zip(quantities)
+        .filter(|&(_, quantity)| quantity > 0)
+        .map(|(tile, quantity)| FoodSource {
+            x: tile.x as usize * TILE_SIZE + TILE_SIZE / 2,
+            y: tile.y as usize * TILE_SIZE + TILE_SIZE / 2,
+            strength: quantity as f32 / richest as f32,
+        })
+        .collect()
+}
+
+/// The Physarum polycephalum slime-mould model: agents that follow and lay trail on a map
+/// that spreads and decays, drawn into networks between food sources on resource-rich tiles
+pub struct Physarum {
+    pub width: usize,
+    pub height: usize,
+    pub config: PhysarumConfig,
+    pub agents: Vec<Agent>,
+    pub food: Vec<FoodSource>,
+    trail: PheromoneField,
+    /// Ticks since the agents were placed
+    pub tick: u64,
+    /// Only used to place agents, stepping them draws from `tick_random` so it's the same on any
+    /// number of threads
+    rng: SimRng,
+    /// Seed the current state was generated from
+    pub seed: u64,
+    /// Assert `assert_invariants` after every update, only honoured in debug builds
+    pub check_invariants: bool,
+}
+
+/// What `Physarum::save` writes
+#[derive(Serialize, Deserialize)]
+struct PhysarumSave {
+    width: usize,
+    height: usize,
+    seed: u64,
+    tick: u64,
+    config: PhysarumConfig,
+    agents: Vec<Agent>,
+    food: Vec<FoodSource>,
+    trail: Vec<f32>,
+    rng: SimRng,
+}
+
+impl Physarum {
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(width != 0 && height != 0);
+        Self {
+            width,
+            height,
+            config: PhysarumConfig::default(),
+            agents: vec![],
+            food: vec![],
+            trail: PheromoneField::new(width, height),
+            tick: 0,
+            rng: SimRng::seed_from_u64(0),
+            seed: 0,
+            check_invariants: false,
+        }
+    }
+
+    /// Scatter `config.density` agents per pixel, facing every which way
+    fn generate_agents(&mut self) {
+        let count = (self.width * self.height) as f32 * self.config.density;
+        let (width, height) = (self.width as f32, self.height as f32);
+        self.agents = (0..count as usize)
+            .map(|_| Agent { x: self.rng.gen_range(0.0..width), y: self.rng.gen_range(0.0..height), heading: self.rng.gen_range(0.0..TAU) })
+            .collect();
+    }
+
+    pub fn assert_invariants(&self) {
+        for (i, agent) in self.agents.iter().enumerate() {
+            assert!(
+                agent.x >= 0.0 && agent.y >= 0.0 && agent.x < self.width as f32 && agent.y < self.height as f32,
+                "agent {i} left the world at ({}, {})",
+                agent.x,
+                agent.y
+            );
+            assert!((0.0..TAU).contains(&agent.heading), "agent {i} has heading {}", agent.heading);
+        }
+        assert!(self.trail.values().iter().all(|value| value.is_finite() && *value >= 0.0), "trail has a negative or infinite pixel");
+    }
+}
+
+impl Simulation for Physarum {
+    fn name(&self) -> &'static str {
+        "physarum"
+    }
+
+    fn update(&mut self) {
+        let (seed, tick, trail, config) = (self.seed, self.tick, &self.trail, &self.config);
+        self.agents.par_iter_mut().enumerate().for_each(|(i, agent)| {
+            agent.step(trail, config, [tick_random(seed, tick, 2 * i), tick_random(seed, tick, 2 * i + 1)]);
+        });
+        // Deposited in agent order, so sums come out the same however the steps were split up
+        for agent in self.agents.iter() {
+            self.trail.deposit(agent.x, agent.y, self.config.deposit);
+        }
+        for food in self.food.iter() {
+            for y in food.y.saturating_sub(FOOD_RADIUS)..=food.y + FOOD_RADIUS {
+                for x in food.x.saturating_sub(FOOD_RADIUS)..=food.x + FOOD_RADIUS {
+                    self.trail.deposit(x as f32, y as f32, self.config.food_deposit * food.strength);
+                }
+            }
+        }
+        self.trail.diffuse(self.config.diffusion, self.config.decay);
+        self.tick += 1;
+        if cfg!(debug_assertions) && self.check_invariants {
+            self.assert_invariants();
+        }
+    }
+
+    fn draw(&mut self, canvas: &mut dyn Canvas) {
+        let scale = self.config.deposit.max(f32::MIN_POSITIVE) * TRAIL_SCALE;
+        for (i, &value) in self.trail.values().iter().enumerate() {
+            canvas.set_pixel(i % self.width, i / self.width, heat_colour(1.0 - (-value / scale).exp()));
+        }
+        let side = 2 * FOOD_RADIUS + 1;
+        for food in self.food.iter() {
+            canvas.fill_rect(food.x.saturating_sub(FOOD_RADIUS), food.y.saturating_sub(FOOD_RADIUS), side, side, FOOD_COLOUR);
+        }
+    }
+
+    fn randomise(&mut self) {
+        self.randomise_with_seed(generate_seed().0);
+    }
+
+    fn randomise_with_seed(&mut self, seed: u64) {
+        debug!("Randomising physarum with seed {}...", seed);
+        self.seed = seed;
+        self.tick = 0;
+        self.rng = SimRng::seed_from_u64(seed);
+        self.trail = PheromoneField::new(self.width, self.height);
+        self.food = place_food(self.width, self.height, seed);
+        self.generate_agents();
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn stats(&self) -> Vec<(&'static str, String)> {
+        vec![("agents", self.agents.len().to_string()), ("trail", format!("{:.0}", self.trail.total())), ("food", self.food.len().to_string())]
+    }
+
+    fn palette(&self) -> Vec<[u8; 4]> {
+        let mut palette: Vec<[u8; 4]> = (0..HEAT_STEPS).map(|i| heat_colour(i as f32 / (HEAT_STEPS - 1) as f32)).collect();
+        palette.push(FOOD_COLOUR);
+        palette
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let save = PhysarumSave {
+            width: self.width,
+            height: self.height,
+            seed: self.seed,
+            tick: self.tick,
+            config: self.config.clone(),
+            agents: self.agents.clone(),
+            food: self.food.clone(),
+            trail: self.trail.values().to_vec(),
+            rng: self.rng.clone(),
+        };
+        serde_json::to_writer(writer, &save)?;
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let save: PhysarumSave = serde_json::from_reader(reader)?;
+        if save.width == 0 || save.height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "saved physarum has no area"));
+        }
+        let trail = PheromoneField::from_values(save.width, save.height, save.trail)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "saved trail doesn't match the world's size"))?;
+        let check_invariants = self.check_invariants;
+        *self = Self::new(save.width, save.height);
+        self.check_invariants = check_invariants;
+        self.seed = save.seed;
+        self.tick = save.tick;
+        self.config = save.config;
+        self.agents = save.agents;
+        self.food = save.food;
+        self.trail = trail;
+        self.rng = save.rng;
+        Ok(())
+    }
+
+    fn set_check_invariants(&mut self, check_invariants: bool) {
+        self.check_invariants = check_invariants;
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if !PhysarumConfig::OPTIONS.contains(&name) {
+            return Err(format!("{} has no option {name}", self.name()));
+        }
+        self.config.set(name, value)?;
+        if name == "agent_density" {
+            self.generate_agents();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench;
+    use crate::render::golden::{assert_golden, Tolerance};
+    use crate::render::image_buffer::ImageBuffer;
+
+    #[test]
+    fn agents_turn_towards_the_stronger_trail_and_bounce_off_edges() {
+        let config = PhysarumConfig::default();
+        let mut trail = PheromoneField::new(40, 40);
+        let mut agent = Agent { x: 20.0, y: 20.0, heading: 0.0 };
+        // Nothing ahead or to either side, so straight on
+        agent.step(&trail, &config, [0.0, 0.0]);
+        assert_eq!((agent.x, agent.y, agent.heading), (21.0, 20.0, 0.0));
+
+        // The right sensor, below the agent as y grows down, finds trail
+        let right = config.sensor_angle.to_radians();
+        trail.deposit(21.0 + right.cos() * config.sensor_distance, 20.0 + right.sin() * config.sensor_distance, 5.0);
+        agent.step(&trail, &config, [0.0, 0.0]);
+        assert_eq!(agent.heading, config.rotation_angle.to_radians());
+        assert!(agent.y > 20.0);
+
+        let mut agent = Agent { x: 39.5, y: 5.0, heading: 0.0 };
+        agent.step(&PheromoneField::new(40, 40), &config, [0.0, 0.5]);
+        assert_eq!((agent.x, agent.y), (39.5, 5.0));
+        assert_eq!(agent.heading, TAU / 2.0);
+    }
+
+    #[test]
+    fn food_sits_on_tiles_that_grow_resources() {
+        let mut physarum = Physarum::new(160, 128);
+        physarum.randomise_with_seed(4);
+        let mut tiles = SimGrid::new(160, 128, 1);
+        tiles.randomise_with_seed(4);
+        let growing: Vec<(usize, usize)> = tiles
+            .tiles
+            .iter()
+            .filter(|tile| tile.generates_resource != 'n')
+            .map(|tile| (tile.x as usize * TILE_SIZE + TILE_SIZE / 2, tile.y as usize * TILE_SIZE + TILE_SIZE / 2))
+            .collect();
+        assert!(!growing.is_empty() && growing.len() < tiles.tiles.len());
+        assert_eq!(physarum.food.iter().map(|food| (food.x, food.y)).collect::<Vec<_>>(), growing);
+        assert!(physarum.food.iter().all(|food| food.strength > 0.0 && food.strength <= 1.0));
+        assert_eq!(physarum.agents.len(), (160.0 * 128.0 * physarum.config.density) as usize);
+    }
+
+    #[test]
+    fn trail_network_matches_golden() {
+        let mut physarum = Physarum::new(96, 64);
+        physarum.set_check_invariants(true);
+        physarum.randomise_with_seed(3);
+        for _ in 0..80 {
+            physarum.update();
+        }
+        let mut image = ImageBuffer::new(96, 64);
+        physarum.draw(&mut image);
+        assert_golden("physarum", &image, Tolerance::default());
+    }
+
+    #[test]
+    #[ignore]
+    fn physarum_scaling() {
+        for (side, density) in [(500, 0.4), (1000, 1.0)] {
+            let mut physarum = Physarum::new(side, side);
+            physarum.config.density = density;
+            physarum.randomise_with_seed(0);
+            bench::time(&format!("update, {} agents on {side}x{side}", physarum.agents.len()), 10, || physarum.update());
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+use crate::components::particle_config::{parse_at_least, parse_at_most};
+
+/// How particles breed once they have the life force to spare.
+/// Off by default, so only rule effects make particles.
+/// Set from the command line with `--set name=value`, using the names in `OPTIONS`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reproduction {
+    /// Life force a particle needs to breed, `None` turns breeding off
+    pub min_life_force: Option<i8>,
+    /// Life force each child costs its parent, which the child starts with
+    pub cost: i8,
+    /// Time a particle waits between litters, and a child waits before its first
+    pub cooldown: f32,
+    /// Children are placed up to this many pixels from their parent on each axis
+    pub offset: f32,
+    /// Chance each gene of a child mutates, see `Genome::mutate`
+    pub mutation_rate: f32,
+    /// Largest change a mutation makes, relative to the gene's range
+    pub mutation_size: f32,
+    /// Chance a mutation redraws the gene from anywhere in its range instead
+    pub mutation_reset: f32,
+    /// Chance a litter has a second parent, the nearest member of the group within `mate_radius`.
+    /// Each child then takes each gene from either parent.
+    pub crossover: f32,
+    pub mate_radius: f32,
+}
+
+impl Default for Reproduction {
+    fn default() -> Self {
+        Self { min_life_force: None, cost: 20, cooldown: 50.0, offset: 5.0, mutation_rate: 0.1, mutation_size: 0.1, mutation_reset: 0.0, crossover: 0.0, mate_radius: 20.0 }
+    }
+}
+
+impl Reproduction {
+    pub const OPTIONS: &'static [&'static str] = &["breed_at", "birth_cost", "birth_cooldown", "birth_offset", "mutation_rate", "mutation_size", "mutation_reset", "crossover", "mate_radius"];
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "breed_at" => self.min_life_force = if value == "off" { None } else { Some(value.parse().map_err(|_| format!("expected a number up to {} or off, got {value}", i8::MAX))?) },
+            "birth_cost" => self.cost = value.parse().ok().filter(|&cost: &i8| cost > 0).ok_or_else(|| format!("expected a number from 1 to {}, got {value}", i8::MAX))?,
+            "birth_cooldown" => self.cooldown = parse_at_least(value, 0.0)?,
+            "birth_offset" => self.offset = parse_at_least(value, 0.0)?,
+            "mutation_rate" => self.mutation_rate = parse_at_most(value, 0.0, 1.0)?,
+            "mutation_size" => self.mutation_size = parse_at_most(value, 0.0, 1.0)?,
+            "mutation_reset" => self.mutation_reset = parse_at_most(value, 0.0, 1.0)?,
+            "crossover" => self.crossover = parse_at_most(value, 0.0, 1.0)?,
+            "mate_radius" => self.mate_radius = parse_at_least(value, 0.0)?,
+            _ => return Err(format!("unknown option {name}")),
+        }
+        Ok(())
+    }
+
+    /// Check the limits `set` keeps to, for settings that didn't come through it like loaded ones
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cost <= 0 {
+            return Err(format!("birth_cost must be from 1 to {}, got {}", i8::MAX, self.cost));
+        }
+        for (name, value) in [("birth_cooldown", self.cooldown), ("birth_offset", self.offset), ("mate_radius", self.mate_radius)] {
+            if value.is_nan() || value < 0.0 {
+                return Err(format!("{name} must be at least 0, got {value}"));
+            }
+        }
+        let chances = [("mutation_rate", self.mutation_rate), ("mutation_size", self.mutation_size), ("mutation_reset", self.mutation_reset), ("crossover", self.crossover)];
+        for (name, value) in chances {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{name} must be from 0 to 1, got {value}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::boundary::{BoundaryMode, Bounds};
+    use crate::components::particle::Particle;
+    use crate::components::particle_store::ParticleStore;
+    use pixels::wgpu::Color;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn breeding_costs_life_force_and_waits_for_the_cooldown() {
+        let reproduction = Reproduction { min_life_force: Some(40), cost: 20, cooldown: 3.0, offset: 2.0, mutation_rate: 0.0, ..Reproduction::default() };
+        let bounds = Bounds { width: 100.0, height: 100.0, mode: BoundaryMode::Reflect };
+        let mut store = ParticleStore::new(vec![Color::RED, Color::BLUE]);
+        let parent = Particle { x: 10.0, y: 10.0, life_force: 100, birth_rate: 3, speed: 1.5, rule_weights: vec![0.5, -1.0], ..Particle::default() };
+        let handle = store.insert(0, &parent);
+        let (mut rng, mut global_id_count) = (StdRng::seed_from_u64(0), 1);
+        let mut breed = |store: &mut ParticleStore| store.reproduce(&reproduction, 1.0, &bounds, &mut global_id_count, &mut rng);
+
+        breed(&mut store);
+        store.assert_invariants();
+        assert_eq!(store.get(handle).unwrap().life_force, 40);
+        for child in store.group_particles(0).into_iter().skip(1) {
+            assert_eq!((child.life_force, child.speed, child.birth_cooldown), (20, 1.5, 3.0));
+            assert_eq!(child.rule_weights, parent.rule_weights);
+            assert!((child.x - parent.x).abs() <= 2.0 && (child.y - parent.y).abs() <= 2.0);
+        }
+        assert_eq!(store.group_particles(0).iter().map(|p| p.id).collect::<Vec<_>>(), vec![0, 2, 3, 4]);
+
+        for _ in 0..3 {
+            breed(&mut store);
+        }
+        assert_eq!(store.members(0).len(), 4);
+        // Breeds again once the cooldown is over, stopping before it spends the last of its life force
+        breed(&mut store);
+        assert_eq!((store.members(0).len(), store.get(handle).unwrap().life_force), (5, 20));
+    }
+
+    #[test]
+    fn crossover_children_have_both_parents() {
+        let reproduction = Reproduction { min_life_force: Some(40), crossover: 1.0, mate_radius: 5.0, mutation_rate: 0.0, ..Reproduction::default() };
+        let bounds = Bounds { width: 100.0, height: 100.0, mode: BoundaryMode::Wrap };
+        let mut store = ParticleStore::new(vec![Color::RED]);
+        store.insert(0, &Particle { id: 1, x: 1.0, y: 50.0, life_force: 100, birth_rate: 1, ..Particle::default() });
+        // Nearest the shortest way round
+        store.insert(0, &Particle { id: 2, x: 98.0, y: 50.0, life_force: 10, birth_rate: 1, speed: 2.0, ..Particle::default() });
+        store.insert(0, &Particle { id: 3, x: 6.0, y: 50.0, life_force: 10, birth_rate: 1, ..Particle::default() });
+        store.reproduce(&reproduction, 1.0, &bounds, &mut 10, &mut StdRng::seed_from_u64(0));
+        let child = store.group_particles(0).pop().unwrap();
+        assert_eq!(child.lineage.parents, [Some(1), Some(2)]);
+        assert_eq!((child.lineage.founder, child.lineage.generation), (Some(1), 1));
+    }
+
+    #[test]
+    fn validate_keeps_to_the_limits_of_set() {
+        assert_eq!(Reproduction::default().validate(), Ok(()));
+        // Loaded settings skip `set`, and a negative cost would overflow the parent's life force
+        let loaded: Reproduction = serde_json::from_str(r#"{"cost": -100}"#).unwrap();
+        assert!(loaded.validate().is_err());
+        assert!(Reproduction { crossover: 1.5, ..Reproduction::default() }.validate().is_err());
+        assert!(Reproduction { offset: f32::NAN, ..Reproduction::default() }.validate().is_err());
+    }
+}
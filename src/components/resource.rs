@@ -1,4 +1,9 @@
-#[derive(Clone, PartialEq, Debug, Default)]
+use serde::{Deserialize, Serialize};
+
+/// Every kind of resource a tile can grow: food, wood, iron, stone, horses and fish
+pub const RESOURCE_TYPES: [char; 6] = ['f', 'w', 'i', 's', 'h', 'p'];
+
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Resource {
     pub resource_type: char,
     pub quantity: i16,
@@ -10,11 +15,45 @@ impl Resource {
         Self { colour_rgba, quantity: 1, resource_type }
     }
 
+    // Quantities saturate so long runs can't overflow, and never drop below empty
     pub fn add_resource(&mut self, quantity_to_add: i16) {
-        self.quantity += quantity_to_add;
+        self.quantity = self.quantity.saturating_add(quantity_to_add).max(0);
     }
 
     pub fn remove_resource(&mut self, quantity_to_remove: i16) {
-        self.quantity -= quantity_to_remove;
+        self.quantity = self.quantity.saturating_sub(quantity_to_remove).max(0);
+    }
+
+    pub fn assert_invariants(&self) {
+        assert!(self.quantity >= 0, "resource {} has negative quantity {}", self.resource_type, self.quantity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn quantity_stays_in_range(ops in prop::collection::vec((any::<bool>(), any::<i16>()), 0..200)) {
+            let mut resource = Resource::new('f', [0, 0, 0, 255]);
+            for (add, quantity) in ops {
+                if add {
+                    resource.add_resource(quantity);
+                } else {
+                    resource.remove_resource(quantity);
+                }
+                resource.assert_invariants();
+            }
+        }
+
+        #[test]
+        fn adding_past_the_limit_saturates(start in 0..i16::MAX, quantity in 0..i16::MAX) {
+            let mut resource = Resource::new('w', [0, 0, 0, 255]);
+            resource.quantity = start;
+            resource.add_resource(quantity);
+            prop_assert_eq!(resource.quantity as i32, (start as i32 + quantity as i32).min(i16::MAX as i32));
+        }
     }
-}
\ No newline at end of file
+}
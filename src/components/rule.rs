@@ -0,0 +1,50 @@
+use pixels::wgpu::Color;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::components::force_kernel::Kernel;
+use crate::components::particle::ColorDef;
+use crate::components::rule_effect::{deserialize_effects, Effect, RuleEffect};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Rule {
+    #[serde(with = "ColorDef")]
+    pub particle_group_one_colour: Color,
+    #[serde(with = "ColorDef")]
+    pub particle_group_two_colour: Color,
+    pub g: f32,
+    /// Overrides `ParticleConfig::kernel` for this rule
+    #[serde(default)]
+    pub kernel: Option<Kernel>,
+    /// Overrides `ParticleConfig::interaction_radius` for this rule
+    #[serde(default)]
+    pub radius: Option<f32>,
+    /// Applied in order wherever a particle of group one touches one of group two
+    #[serde(default, alias = "effect", deserialize_with = "deserialize_effects")]
+    pub effects: Vec<RuleEffect>,
+}
+
+impl Rule {
+    pub fn new<R: Rng>(particle_group_one_colour: Color, particle_group_two_colour: Color, effect_allowed: bool, rng: &mut R) -> Self {
+        Self {
+            particle_group_one_colour,
+            particle_group_two_colour,
+            g: rng.gen_range(0.0..1.0),
+            kernel: None,
+            radius: None,
+            effects: Self::assign_random_effect(effect_allowed, rng),
+        }
+    }
+
+    fn assign_random_effect<R: Rng>(effect_allowed: bool, rng: &mut R) -> Vec<RuleEffect> {
+        if !effect_allowed {
+            return vec![];
+        }
+        let random_number: f32 = rng.gen_range(0.0..1.0);
+        if random_number < 0.5 {
+            vec![RuleEffect::new(Effect::Spawn { offset: 100.0 })]
+        } else {
+            vec![]
+        }
+    }
+}
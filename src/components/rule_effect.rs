@@ -0,0 +1,204 @@
+use rand::Rng;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::components::boundary::Bounds;
+use crate::components::particle_store::{Bond, ParticleHandle, ParticleStore};
+use crate::MAX_PARTICLES_PER_GROUP;
+
+/// Something that happens when a particle of a rule's group touches one of the group it follows
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Effect {
+    /// The particle has `birth_rate` children up to `offset` pixels away on each axis
+    Spawn { offset: f32 },
+    /// The particle takes up to `amount` life force from the other
+    Consume { amount: i8 },
+    /// The other particle joins the particle's group, taking its colour
+    Convert,
+    /// Both particles are knocked apart with this speed
+    Repel { strength: f32 },
+    /// The particles are joined by a spring pulling them to `length` apart, which breaks when
+    /// stretched past `max_length`. Neither particle takes more than `max_bonds` bonds.
+    Bond { strength: f32, length: f32, max_length: f32, max_bonds: u8 },
+}
+
+/// An effect and the chance it happens on each contact
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RuleEffect {
+    #[serde(flatten)]
+    pub effect: Effect,
+    #[serde(default = "always")]
+    pub probability: f32,
+}
+
+fn always() -> f32 {
+    1.0
+}
+
+/// Two particles touching, see `ParticleStore::contacts`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Contact {
+    pub particle: ParticleHandle,
+    pub other: ParticleHandle,
+    /// From the other particle to this one, the shortest way round when wrapping
+    pub dx: f32,
+    pub dy: f32,
+}
+
+impl RuleEffect {
+    pub fn new(effect: Effect) -> Self {
+        Self { effect, probability: 1.0 }
+    }
+
+    /// Roll for the effect and apply it to `contact`, where the particle belongs to `group`
+    pub fn apply<R: Rng>(&self, store: &mut ParticleStore, group: usize, contact: Contact, bounds: &Bounds, global_id_count: &mut u32, rng: &mut R) {
+        // Certain effects don't draw from the generator, so they don't disturb anything else that does
+        if self.probability < 1.0 && rng.gen::<f32>() >= self.probability {
+            return;
+        }
+        let (Some(slot), Some(other)) = (store.slot(contact.particle), store.slot(contact.other)) else {
+            return;
+        };
+        match self.effect {
+            Effect::Spawn { offset } => {
+                // Only as many children as fit under the cap are made, so none use up ids or
+                // random draws only to be thrown away
+                let room = MAX_PARTICLES_PER_GROUP.saturating_sub(store.members(group).len());
+                let mut parent = store.get(contact.particle).expect("slot is live");
+                for child in parent.spawn_children(offset, room, bounds, global_id_count, rng) {
+                    store.insert(group, &child);
+                }
+            },
+            Effect::Consume { amount } => {
+                let taken = amount.min(store.life_force[other]).max(0);
+                store.life_force[other] -= taken;
+                store.life_force[slot] = store.life_force[slot].saturating_add(taken);
+            },
+            Effect::Convert => {
+                if store.group_of(contact.other) != Some(group) && store.members(group).len() < MAX_PARTICLES_PER_GROUP {
+                    store.move_to_group(contact.other, group);
+                }
+            },
+            Effect::Repel { strength } => {
+                let d = (contact.dx * contact.dx + contact.dy * contact.dy).sqrt();
+                // Particles on exactly the same spot part in a random direction
+                let (ux, uy) = if d > 0.0 {
+                    (contact.dx / d, contact.dy / d)
+                } else {
+                    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                    (angle.cos(), angle.sin())
+                };
+                store.vx[slot] += strength * ux;
+                store.vy[slot] += strength * uy;
+                store.vx[other] -= strength * ux;
+                store.vy[other] -= strength * uy;
+            },
+            Effect::Bond { strength, length, max_length, max_bonds } => {
+                store.add_bond(Bond { a: contact.particle, b: contact.other, strength, length, max_length }, max_bonds);
+            },
+        }
+    }
+}
+
+/// Reads a rule's effects, also accepting the single effect name older saves used
+pub fn deserialize_effects<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<RuleEffect>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Effects {
+        Legacy(String),
+        Typed(Vec<RuleEffect>),
+    }
+    match Effects::deserialize(deserializer)? {
+        Effects::Typed(effects) => Ok(effects),
+        Effects::Legacy(name) => match name.as_str() {
+            "nothing" => Ok(vec![]),
+            "spawn_children" => Ok(vec![RuleEffect::new(Effect::Spawn { offset: 100.0 })]),
+            _ => Err(serde::de::Error::custom(format!("unknown effect {name}"))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::boundary::BoundaryMode;
+    use crate::components::particle::Particle;
+    use pixels::wgpu::Color;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const BOUNDS: Bounds = Bounds { width: 100.0, height: 100.0, mode: BoundaryMode::Reflect };
+
+    /// Two groups with one particle each, touching
+    fn touching() -> (ParticleStore, Contact) {
+        let mut store = ParticleStore::new(vec![Color { r: 255.0, g: 0.0, b: 0.0, a: 255.0 }, Color { r: 0.0, g: 0.0, b: 255.0, a: 255.0 }]);
+        let particle = store.insert(0, &Particle { id: 1, x: 10.0, y: 10.0, life_force: 50, birth_rate: 2, ..Particle::default() });
+        let other = store.insert(1, &Particle { id: 2, x: 10.0, y: 10.0, life_force: 30, ..Particle::default() });
+        (store, Contact { particle, other, dx: 0.0, dy: 0.0 })
+    }
+
+    fn apply(effect: Effect, store: &mut ParticleStore, contact: Contact) {
+        RuleEffect::new(effect).apply(store, 0, contact, &BOUNDS, &mut 10, &mut StdRng::seed_from_u64(0));
+    }
+
+    #[test]
+    fn consume_moves_life_force() {
+        let (mut store, contact) = touching();
+        apply(Effect::Consume { amount: 20 }, &mut store, contact);
+        assert_eq!((store.get(contact.particle).unwrap().life_force, store.get(contact.other).unwrap().life_force), (70, 10));
+        apply(Effect::Consume { amount: 20 }, &mut store, contact);
+        assert_eq!((store.get(contact.particle).unwrap().life_force, store.get(contact.other).unwrap().life_force), (80, 0));
+    }
+
+    #[test]
+    fn convert_moves_the_other_particle_into_the_group() {
+        let (mut store, contact) = touching();
+        apply(Effect::Convert, &mut store, contact);
+        store.assert_invariants();
+        assert_eq!(store.group_of(contact.other), Some(0));
+        assert_eq!(store.members(1).len(), 0);
+        assert_eq!(store.get(contact.other).unwrap().pixel_colour_rgba, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn spawn_repel_and_bond() {
+        let (mut store, contact) = touching();
+        apply(Effect::Spawn { offset: 5.0 }, &mut store, contact);
+        assert_eq!(store.members(0).len(), 3);
+        // Each child of the litter gets its own spot
+        let children = store.group_particles(0).split_off(1);
+        assert_ne!((children[0].x, children[0].y), (children[1].x, children[1].y));
+
+        apply(Effect::Repel { strength: 2.0 }, &mut store, Contact { dx: 1.0, ..contact });
+        let (particle, other) = (store.get(contact.particle).unwrap(), store.get(contact.other).unwrap());
+        assert_eq!((particle.vx, other.vx), (2.0, -2.0));
+
+        apply(Effect::Bond { strength: 0.1, length: 3.0, max_length: 10.0, max_bonds: 1 }, &mut store, contact);
+        apply(Effect::Bond { strength: 0.1, length: 3.0, max_length: 10.0, max_bonds: 1 }, &mut store, contact);
+        assert_eq!(store.bonds().len(), 1);
+        store.assert_invariants();
+    }
+
+    #[test]
+    fn spawn_stops_at_the_group_cap() {
+        let (mut store, contact) = touching();
+        while store.members(0).len() < MAX_PARTICLES_PER_GROUP - 1 {
+            store.insert(0, &Particle::default());
+        }
+        // A litter of two with room for one, only the one that fits gets an id
+        let mut global_id_count = 10;
+        RuleEffect::new(Effect::Spawn { offset: 5.0 }).apply(&mut store, 0, contact, &BOUNDS, &mut global_id_count, &mut StdRng::seed_from_u64(0));
+        assert_eq!((store.members(0).len(), global_id_count), (MAX_PARTICLES_PER_GROUP, 11));
+        apply(Effect::Spawn { offset: 5.0 }, &mut store, contact);
+        assert_eq!(store.members(0).len(), MAX_PARTICLES_PER_GROUP);
+    }
+
+    #[test]
+    fn reads_old_and_new_effects() {
+        let read = |json: &str| deserialize_effects(&mut serde_json::Deserializer::from_str(json)).unwrap();
+        assert_eq!(read(r#""nothing""#), vec![]);
+        assert_eq!(read(r#""spawn_children""#), vec![RuleEffect::new(Effect::Spawn { offset: 100.0 })]);
+        let effects = vec![RuleEffect { effect: Effect::Consume { amount: 5 }, probability: 0.5 }, RuleEffect::new(Effect::Convert)];
+        assert_eq!(read(&serde_json::to_string(&effects).unwrap()), effects);
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::components::force_kernel::Kernel;
+use crate::components::particle_config::ParticleConfig;
+use crate::components::rule_effect::{deserialize_effects, RuleEffect};
+
+/// Where the window's preset editor saves presets, and where `find` looks for them by name
+pub const PRESET_DIRECTORY: &str = "presets";
+
+/// Presets shipped with the simulator, each known for a particular emergent behaviour
+pub const BUILT_IN_PRESETS: &[(&str, &str)] = &[
+    ("cells", include_str!("../../presets/cells.json")),
+    ("snakes", include_str!("../../presets/snakes.json")),
+    ("orbits", include_str!("../../presets/orbits.json")),
+];
+
+/// A particle world's rules by group index rather than colour, so they can be shared
+/// between worlds and saved to their own files
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RulePreset {
+    pub name: String,
+    pub groups: usize,
+    /// Group colours, random ones are kept or generated when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub colours: Vec<[u8; 3]>,
+    /// Replaces the world's config when given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<ParticleConfig>,
+    /// Pairs without a rule don't push each other
+    pub rules: Vec<PresetRule>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PresetRule {
+    /// Group the rule moves
+    pub one: usize,
+    /// Group it moves `one` relative to
+    pub two: usize,
+    pub g: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel: Option<Kernel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radius: Option<f32>,
+    #[serde(default, alias = "effect", deserialize_with = "deserialize_effects", skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<RuleEffect>,
+}
+
+impl RulePreset {
+    pub fn built_in_names() -> Vec<&'static str> {
+        BUILT_IN_PRESETS.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// The built in preset called `name`, else the one saved as `name` in `PRESET_DIRECTORY`,
+    /// else the preset file at path `name`
+    pub fn find(name: &str) -> Result<Self, String> {
+        if let Some((_, json)) = BUILT_IN_PRESETS.iter().find(|(built_in, _)| *built_in == name) {
+            return serde_json::from_str(json).map_err(|err| format!("built in preset {name} is invalid: {err}"));
+        }
+        let saved = Self::path_for(name);
+        let path = if saved.exists() { saved } else { PathBuf::from(name) };
+        Self::load(&path).map_err(|err| format!("couldn't load preset {name} from {}: {err}", path.display()))
+    }
+
+    /// Names of the built in presets followed by any saved in `PRESET_DIRECTORY`, for cycling through
+    pub fn available_names() -> Vec<String> {
+        let mut names: Vec<String> = Self::built_in_names().into_iter().map(String::from).collect();
+        let mut saved: Vec<String> = std::fs::read_dir(PRESET_DIRECTORY)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .filter(|name| !names.contains(name))
+            .collect();
+        saved.sort();
+        names.append(&mut saved);
+        names
+    }
+
+    /// Where a preset called `name` is saved
+    pub fn path_for(name: &str) -> PathBuf {
+        Path::new(PRESET_DIRECTORY).join(format!("{name}.json"))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let preset: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        preset.validate().map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+        Ok(preset)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.groups == 0 {
+            return Err(format!("preset {} has no groups", self.name));
+        }
+        if let Some(rule) = self.rules.iter().find(|rule| rule.one >= self.groups || rule.two >= self.groups) {
+            return Err(format!("preset {} has a rule between groups {} and {}, but only {} groups", self.name, rule.one, rule.two, self.groups));
+        }
+        if !self.colours.is_empty() && self.colours.len() != self.groups {
+            return Err(format!("preset {} has {} colours for {} groups", self.name, self.colours.len(), self.groups));
+        }
+        // Rules refer to groups by colour once applied
+        if self.colours.iter().enumerate().any(|(i, colour)| self.colours[..i].contains(colour)) {
+            return Err(format!("preset {} gives two groups the same colour", self.name));
+        }
+        if let Some(config) = &self.config {
+            config.metabolism.validate().map_err(|err| format!("preset {} metabolism: {err}", self.name))?;
+            config.reproduction.validate().map_err(|err| format!("preset {} reproduction: {err}", self.name))?;
+        }
+        Ok(())
+    }
+
+    /// Strength of the rule moving `one` relative to `two`, 0 if there isn't one
+    pub fn g(&self, one: usize, two: usize) -> f32 {
+        self.rules.iter().find(|rule| rule.one == one && rule.two == two).map_or(0.0, |rule| rule.g)
+    }
+
+    /// Set the strength of the rule moving `one` relative to `two`, adding the rule if needed
+    pub fn set_g(&mut self, one: usize, two: usize, g: f32) {
+        match self.rules.iter_mut().find(|rule| rule.one == one && rule.two == two) {
+            Some(rule) => rule.g = g,
+            None => self.rules.push(PresetRule { one, two, g, kernel: None, radius: None, effects: vec![] }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::life_grid::LifeGrid;
+    use crate::simulation::Simulation;
+
+    #[test]
+    fn built_in_presets_are_valid() {
+        for name in RulePreset::built_in_names() {
+            let preset = RulePreset::find(name).unwrap();
+            assert_eq!(preset.name, name);
+            preset.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn particle_worlds_give_back_the_preset_they_were_given() {
+        let preset = RulePreset::find("orbits").unwrap();
+        let mut world = LifeGrid::new(120, 80, 8);
+        world.randomise_with_seed(3);
+        world.apply_preset(&preset).unwrap();
+        assert_eq!(world.rule_preset(), Some(preset.clone()));
+        assert_eq!(world.particles.len(), 3 * 150);
+
+        // Changing only the rules keeps the particles where they are
+        let mut edited = preset;
+        edited.set_g(0, 1, 0.5);
+        let positions = world.particles.x.clone();
+        world.apply_preset(&edited).unwrap();
+        assert_eq!(world.particles.x, positions);
+        assert_eq!(world.rule_preset().unwrap().g(0, 1), 0.5);
+    }
+
+    #[test]
+    fn set_g_adds_missing_rules() {
+        let mut preset = RulePreset::find("cells").unwrap();
+        preset.rules.clear();
+        assert_eq!(preset.g(1, 0), 0.0);
+        preset.set_g(1, 0, -0.5);
+        preset.set_g(1, 0, 0.25);
+        assert_eq!(preset.g(1, 0), 0.25);
+        assert_eq!(preset.rules.len(), 1);
+    }
+
+    #[test]
+    fn presets_with_out_of_range_settings_are_rejected() {
+        let mut preset = RulePreset::find("cells").unwrap();
+        let mut config = preset.config.clone().unwrap_or_default();
+        config.metabolism.food_energy = 0;
+        preset.config = Some(config.clone());
+        assert!(preset.validate().is_err());
+        assert!(LifeGrid::new(64, 48, 3).apply_preset(&preset).is_err());
+
+        config.metabolism.food_energy = 1;
+        config.reproduction.cost = -100;
+        preset.config = Some(config);
+        assert!(preset.validate().is_err());
+    }
+}
@@ -1,22 +1,103 @@
-use bevy_math::UVec2;
-use pixels::wgpu::Color;
-use crate::generate_seed;
-use rand::Rng;
+use std::io::{self, Read, Write};
+
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
-// FIXME: legacy
+use crate::components::boundary::{BoundaryMode, Bounds};
+use crate::components::genome::{MAX_PREFERENCE, SENSING_RANGE};
+use crate::components::habitat::{Habitat, HABITATS};
+use crate::components::metabolism::{eat, Metabolism};
 use crate::components::particle::Particle;
+use crate::components::particle_group::ParticleGroup;
+use crate::components::particle_store::ParticleStore;
+use crate::components::pheromone::{PheromoneConfig, PheromoneField, PHEROMONE_COLOUR};
+use crate::components::reproduction::Reproduction;
+use crate::components::resource::RESOURCE_TYPES;
+use crate::components::tile::{Tile, TILE_SIZE};
+use crate::render::Canvas;
+use crate::simulation::{loaded_rng, SimRng, Simulation};
+use crate::generate_seed;
 
-use crate::components::tile::Tile;
+/// Particles of each habitat `randomise` places on the tiles
+const WALKERS_PER_HABITAT: usize = 100;
+/// Largest random change to a walker's velocity each tick
+const WANDER: f32 = 0.3;
+/// Fastest a walker moves over open ground, in pixels per tick
+const MAX_WALK_SPEED: f32 = 1.0;
+/// Change to a walker's velocity each tick towards the resources it prefers
+const CHEMOTAXIS_PULL: f32 = 0.5;
+/// Pixels to either side a walker compares pheromone over
+const PHEROMONE_SENSE_DISTANCE: f32 = 3.0;
+/// `Metabolism` options that apply to walkers, the rest are about food tiles the tile world doesn't have
+const WALKER_METABOLISM_OPTIONS: [&str; 7] = ["movement_cost", "basal_cost", "ageing_cost", "max_age", "harvest", "food_energy", "death_drop"];
 
 pub struct SimGrid {
     pub width: usize,
     pub height: usize,
+    // FIXME: not read by the tile simulation yet
+    #[allow(dead_code)]
     pub pixel_size: usize,
+    #[allow(dead_code)]
     pub x: u8,
+    #[allow(dead_code)]
     pub y: u8,
-    pub pixels: Vec<Particle>,
     pub tiles: Vec<Tile>,
-    pub needsReRender: bool,
+    /// Particles living on the tiles, one group per `Habitat` in `HABITATS` order
+    pub particles: ParticleStore,
+    /// Walkers of each habitat `randomise` places
+    pub walkers_per_habitat: usize,
+    /// How walkers burn life force, and how much they eat from their tile's resources
+    pub metabolism: Metabolism,
+    /// How walkers breed, off by default. Their children inherit mutated sensing and
+    /// preferences, so breeding lets foraging evolve.
+    pub reproduction: Reproduction,
+    /// Last id given to a walker
+    pub global_id_count: u32,
+    /// Ticks since the tiles were generated
+    pub ticks: u64,
+    /// How walkers lay and follow trails in `pheromones`
+    pub pheromone: PheromoneConfig,
+    /// Trails walkers have laid, not saved so they start again on load.
+    /// Empty until `pheromone` turns it on.
+    pheromones: PheromoneField,
+    rng: SimRng,
+    pub needs_re_render: bool,
+    /// Seed the current tiles were generated from
+    pub seed: u64,
+    /// Assert `assert_invariants` after every update, only honoured in debug builds
+    pub check_invariants: bool,
+}
+
+/// What `SimGrid::save` writes, everything else is rebuilt on load
+#[derive(Serialize, Deserialize)]
+struct SimGridSave {
+    width: usize,
+    height: usize,
+    pixel_size: usize,
+    seed: u64,
+    tiles: Vec<Tile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    walkers: Vec<ParticleGroup>,
+    #[serde(default)]
+    metabolism: Metabolism,
+    #[serde(default)]
+    reproduction: Reproduction,
+    #[serde(default)]
+    global_id_count: u32,
+    #[serde(default)]
+    ticks: u64,
+    #[serde(default)]
+    pheromone: PheromoneConfig,
+    #[serde(default)]
+    rng: Option<SimRng>,
+    #[serde(default = "default_walkers_per_habitat")]
+    walkers_per_habitat: usize,
+}
+
+fn default_walkers_per_habitat() -> usize {
+    WALKERS_PER_HABITAT
 }
 
 impl SimGrid {
@@ -28,104 +109,685 @@ impl SimGrid {
             width,
             height,
             pixel_size,
-            pixels: vec![Particle::default(); width * height],
-            tiles: vec![Tile::default()],
-            needsReRender: true,
+            tiles: vec![],
+            particles: ParticleStore::new(HABITATS.iter().map(Habitat::colour).collect()),
+            walkers_per_habitat: WALKERS_PER_HABITAT,
+            // Walkers slowly starve unless they find something to eat
+            metabolism: Metabolism { basal_cost: 0.1, ..Metabolism::default() },
+            reproduction: Reproduction::default(),
+            global_id_count: 0,
+            ticks: 0,
+            pheromone: PheromoneConfig::default(),
+            pheromones: PheromoneField::default(),
+            rng: SimRng::seed_from_u64(0),
+            needs_re_render: true,
+            seed: 0,
+            check_invariants: false,
         }
     }
 
-    pub fn render_screen(&mut self, screen: &mut [u8]) {
-        let mut temp_pixel_colours_from_tiles: Vec<[u8; 4]> = vec![];
-        // Render tiles into screen
+    pub fn render_screen(&mut self, canvas: &mut dyn Canvas) {
+        canvas.clear(Tile::terrain_colour('.'));
+        // Render tiles into screen, each tile knows its own position on the tile grid
         for tile in self.tiles.iter() {
-            for p in tile.pixels.iter() {
-                temp_pixel_colours_from_tiles.push(p.colour_rgba);
+            canvas.blit_sprite(tile.x as usize * TILE_SIZE, tile.y as usize * TILE_SIZE, TILE_SIZE, &tile.pixels);
+        }
+
+        // Stop unnecessary re-renders
+        self.needs_re_render = false;
+    }
+
+    /// Draw every walker over the tiles, faded by its life force
+    fn draw_walkers(&self, canvas: &mut dyn Canvas) {
+        for group in 0..self.particles.group_count() {
+            for &slot in self.particles.members(group) {
+                canvas.blend_pixel(self.particles.x[slot] as usize, self.particles.y[slot] as usize, self.particles.rgba[slot], self.particles.alpha(slot));
             }
         }
-        println!("temp_pixels_from_tiles: {}", temp_pixel_colours_from_tiles.len());
-
-        let res: usize = 32;
-        let width_res: i32 = res as i32;
-        let height_res: i32 = res as i32;
-        // let tile_res = width_res * height_res;
-        let width_res_fit: i32 = (self.width / res) as i32;
-        println!("width_res_fit: {}", width_res_fit);
-        let height_res_fit: i32 = (self.height / res) as i32;
-        
-        for (tile_i, tile) in self.tiles.iter().enumerate() {
-            println!("tile_i: {}", tile_i);
-            let mut calculated_x = 0;
-            let mut calculated_y = 0;
-            if (tile_i as i32 + 1) > height_res_fit {
-                calculated_y = (tile_i as i32 / height_res_fit) * height_res;
+    }
+
+    /// Index of the tile below position (`x`, `y`), if there is one
+    fn tile_at(tiles: &[Tile], width: usize, x: f32, y: f32) -> Option<usize> {
+        let columns = width / TILE_SIZE;
+        if x < 0.0 || y < 0.0 || x as usize / TILE_SIZE >= columns {
+            return None;
+        }
+        let i = (y as usize / TILE_SIZE) * columns + x as usize / TILE_SIZE;
+        (i < tiles.len()).then_some(i)
+    }
+
+    fn terrain_at(&self, x: f32, y: f32) -> Option<char> {
+        Self::tile_at(&self.tiles, self.width, x, y).map(|i| self.tiles[i].terrain_type)
+    }
+
+    /// Replace every walker with `walkers_per_habitat` new ones on random tiles of each habitat
+    fn generate_walkers(&mut self) {
+        self.particles = ParticleStore::new(HABITATS.iter().map(Habitat::colour).collect());
+        self.global_id_count = 0;
+        for (group, habitat) in HABITATS.iter().enumerate() {
+            let homes: Vec<&Tile> = self.tiles.iter().filter(|tile| habitat.can_enter(tile.terrain_type)).collect();
+            if homes.is_empty() {
+                continue;
             }
-            let mut shared = false;
-            for (p_i, p) in tile.pixels.iter().enumerate() {
-                // println!("p_i: {}", p_i);
-                if shared == false {
-                    shared = true;
-                    println!("loaded tile_i from second if: {}", tile_i);
+            let colour = habitat.colour();
+            let rgba = [colour.r as u8, colour.g as u8, colour.b as u8, colour.a as u8];
+            for _ in 0..self.walkers_per_habitat {
+                let home = homes[self.rng.gen_range(0..homes.len())];
+                let x = (home.x as usize * TILE_SIZE) as f32 + self.rng.gen_range(0.0..TILE_SIZE as f32);
+                let y = (home.y as usize * TILE_SIZE) as f32 + self.rng.gen_range(0.0..TILE_SIZE as f32);
+                self.global_id_count += 1;
+                let mut walker = Particle::new(rgba, self.global_id_count, x, y, 0.0, 0.0, colour, 1, &mut self.rng);
+                // Every walker is drawn to what it eats, and a little to or from everything else
+                walker.sensing = self.rng.gen_range(SENSING_RANGE.0..=SENSING_RANGE.1 / 2.0);
+                walker.preferences = RESOURCE_TYPES
+                    .iter()
+                    .map(|&resource_type| if habitat.eats(resource_type) { self.rng.gen_range(MAX_PREFERENCE / 2.0..=MAX_PREFERENCE) } else { self.rng.gen_range(-MAX_PREFERENCE / 4.0..=MAX_PREFERENCE / 4.0) })
+                    .collect();
+                self.particles.insert(group, &walker);
+            }
+        }
+    }
+
+    /// Direction, of length 1 or 0, up the gradient of how much `preferences` draw a walker of
+    /// `habitat` at (`x`, `y`) to the resources of the tiles it can enter within `sensing` tiles
+    #[allow(clippy::too_many_arguments)]
+    fn resource_gradient(tiles: &[Tile], width: usize, height: usize, habitat: Habitat, x: f32, y: f32, sensing: f32, preferences: &[f32; RESOURCE_TYPES.len()]) -> (f32, f32) {
+        let Some(here) = Self::tile_at(tiles, width, x, y) else {
+            return (0.0, 0.0);
+        };
+        // Big piles only count for a little more than small ones
+        let appeal = |tile: &Tile| -> f32 {
+            tile.resources.iter().filter_map(|resource| Some(preferences[RESOURCE_TYPES.iter().position(|&t| t == resource.resource_type)?] * (resource.quantity as f32).ln_1p())).sum()
+        };
+        let (columns, rows) = ((width / TILE_SIZE) as i64, (height / TILE_SIZE) as i64);
+        let (column, row) = (tiles[here].x as i64, tiles[here].y as i64);
+        let here_appeal = appeal(&tiles[here]);
+        let reach = sensing as i64;
+        let (mut gx, mut gy) = (0.0, 0.0);
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let distance_squared = (dx * dx + dy * dy) as f32;
+                let (other_column, other_row) = (column + dx, row + dy);
+                if distance_squared == 0.0 || distance_squared > sensing * sensing || !(0..columns).contains(&other_column) || !(0..rows).contains(&other_row) {
+                    continue;
                 }
-                if p_i as i32 <= (width_res - 1) {
-                    calculated_x = p_i as i32 + ((tile_i as i32 % width_res_fit) * width_res);
+                let Some(other) = tiles.get((other_row * columns + other_column) as usize).filter(|tile| habitat.can_enter(tile.terrain_type)) else {
+                    continue;
+                };
+                let difference = appeal(other) - here_appeal;
+                gx += difference * dx as f32 / distance_squared;
+                gy += difference * dy as f32 / distance_squared;
+            }
+        }
+        let length = (gx * gx + gy * gy).sqrt();
+        if length > 0.0 {
+            (gx / length, gy / length)
+        } else {
+            (0.0, 0.0)
+        }
+    }
+
+    /// Move every walker a random step plus pulls up its resource gradient and the pheromone
+    /// field, as far as the terrain it's on lets it. Walkers turn back rather than step onto a
+    /// tile their habitat can't enter.
+    fn walk(&mut self) {
+        let (tiles, width, height, particles, rng) = (&self.tiles, self.width, self.height, &mut self.particles, &mut self.rng);
+        let (pheromones, follow) = (&self.pheromones, if self.pheromone.enabled() { self.pheromone.follow } else { 0.0 });
+        let terrain_at = |x: f32, y: f32| Self::tile_at(tiles, width, x, y).map(|i| tiles[i].terrain_type);
+        for (group, &habitat) in HABITATS.iter().enumerate() {
+            for i in 0..particles.members(group).len() {
+                let slot = particles.members(group)[i];
+                let (pull_x, pull_y) = Self::resource_gradient(tiles, width, height, habitat, particles.x[slot], particles.y[slot], particles.sensing[slot], &particles.preferences[slot]);
+                let (trail_x, trail_y) = if follow > 0.0 { pheromones.gradient(particles.x[slot], particles.y[slot], PHEROMONE_SENSE_DISTANCE) } else { (0.0, 0.0) };
+                let vx = (particles.vx[slot] + rng.gen_range(-WANDER..=WANDER) + pull_x * CHEMOTAXIS_PULL + trail_x * follow).clamp(-MAX_WALK_SPEED, MAX_WALK_SPEED);
+                let vy = (particles.vy[slot] + rng.gen_range(-WANDER..=WANDER) + pull_y * CHEMOTAXIS_PULL + trail_y * follow).clamp(-MAX_WALK_SPEED, MAX_WALK_SPEED);
+                let speed = terrain_at(particles.x[slot], particles.y[slot]).map_or(0.0, |terrain| habitat.speed_on(terrain)) * particles.speed[slot];
+                let (x, y) = (particles.x[slot] + vx * speed, particles.y[slot] + vy * speed);
+                if terrain_at(x, y).is_some_and(|terrain| habitat.can_enter(terrain)) {
+                    (particles.x[slot], particles.y[slot]) = (x, y);
+                    (particles.vx[slot], particles.vy[slot]) = (vx, vy);
                 } else {
-                    calculated_x = (p_i as i32 % width_res) + ((tile_i as i32 % width_res_fit) * width_res);
-                    if (p_i as i32 % width_res) == 0 {
-                        calculated_y += 1;
-                    }
+                    (particles.vx[slot], particles.vy[slot]) = (-vx, -vy);
                 }
-                let pixel_colour = p.colour_rgba;
-                screen[(calculated_y as usize * self.width + calculated_x as usize) * 4..(calculated_y as usize * self.width + calculated_x as usize) * 4 + 4].copy_from_slice(&pixel_colour);
             }
         }
+    }
 
-        // Stop unnecessary re-renders
-        self.needsReRender = false;
+    /// Let every walker `eat` what its habitat eats from its tile
+    fn harvest(&mut self) {
+        let (harvest, food_energy) = (self.metabolism.harvest, self.metabolism.food_energy);
+        for (group, habitat) in HABITATS.iter().enumerate() {
+            for i in 0..self.particles.members(group).len() {
+                let slot = self.particles.members(group)[i];
+                let Some(tile) = Self::tile_at(&self.tiles, self.width, self.particles.x[slot], self.particles.y[slot]) else {
+                    continue;
+                };
+                let Some(resource) = self.tiles[tile].resources.iter_mut().find(|resource| habitat.eats(resource.resource_type)) else {
+                    continue;
+                };
+                eat(resource, &mut self.particles.life_force[slot], harvest, food_energy);
+            }
+        }
     }
 
-    pub fn draw(&mut self, screen: &mut [u8]) {
-        if self.needsReRender == false {
+    /// Let walkers with the life force to spare breed. Children that land where their habitat
+    /// can't go are stillborn.
+    fn breed(&mut self) {
+        if self.reproduction.min_life_force.is_none() {
             return;
         }
-        self.render_screen(screen);
+        let tiled = |size: usize| (size / TILE_SIZE * TILE_SIZE) as f32;
+        let bounds = Bounds { width: tiled(self.width), height: tiled(self.height), mode: BoundaryMode::Clamp };
+        self.particles.reproduce(&self.reproduction, 1.0, &bounds, &mut self.global_id_count, &mut self.rng);
+        let (tiles, width) = (&self.tiles, self.width);
+        for (group, habitat) in HABITATS.iter().enumerate() {
+            self.particles.remove_where(group, |store, slot| !Self::tile_at(tiles, width, store.x[slot], store.y[slot]).is_some_and(|i| habitat.can_enter(tiles[i].terrain_type)));
+        }
     }
 
-    pub fn update(&mut self) {
+    /// Panics if the grid is in a state the simulation should never reach
+    pub fn assert_invariants(&self) {
+        let width_res_fit = self.width / TILE_SIZE;
+        let height_res_fit = self.height / TILE_SIZE;
+        assert!(self.tiles.len() <= width_res_fit * height_res_fit, "more tiles than fit on screen");
+
+        // Tiles sit fully on screen without overlapping, so each tile pixel maps to exactly one screen pixel
+        let mut covered = vec![false; width_res_fit * height_res_fit];
+        for tile in self.tiles.iter() {
+            tile.assert_invariants();
+            let (x, y) = (tile.x as usize, tile.y as usize);
+            assert!(x < width_res_fit && y < height_res_fit, "tile ({x}, {y}) is off screen");
+            assert!(!covered[y * width_res_fit + x], "two tiles at ({x}, {y})");
+            covered[y * width_res_fit + x] = true;
+        }
+
+        self.particles.assert_invariants();
+        for (group, habitat) in HABITATS.iter().enumerate() {
+            for &slot in self.particles.members(group) {
+                let (x, y) = (self.particles.x[slot], self.particles.y[slot]);
+                assert!(self.terrain_at(x, y).is_some_and(|terrain| habitat.can_enter(terrain)), "{habitat:?} walker {} at ({x}, {y}) is off its habitat", self.particles.id[slot]);
+            }
+        }
+
+        // Saving and loading gives back the same grid
+        let mut saved = vec![];
+        self.save(&mut saved).expect("failed to save grid");
+        let mut loaded = Self::new(self.width, self.height, self.pixel_size);
+        loaded.load(&mut saved.as_slice()).expect("failed to load saved grid");
+        assert!(loaded.seed == self.seed && loaded.tiles == self.tiles, "save/load round trip changed the grid");
+        assert!(
+            (0..HABITATS.len()).all(|group| loaded.particles.group_particles(group) == self.particles.group_particles(group)),
+            "save/load round trip changed the walkers"
+        );
+
+        // The seed still describes the terrain
+        let mut regenerated = Self::new(self.width, self.height, self.pixel_size);
+        regenerated.randomise_with_seed(self.seed);
+        if !self.tiles.is_empty() {
+            for (tile, expected) in self.tiles.iter().zip(regenerated.tiles.iter()) {
+                assert!(
+                    tile.terrain_type == expected.terrain_type && tile.generates_resource == expected.generates_resource,
+                    "tile ({}, {}) doesn't match seed {}", tile.x, tile.y, self.seed,
+                );
+            }
+        }
+    }
+
+    /// Tiles only read their own state while updating, so they can all update in parallel
+    fn tile_lifecycle(&mut self) {
+        self.tiles.par_iter_mut().for_each(|tile| tile.update_tile());
+    }
+
+    /// Every walker lays pheromone on its pixel, more the better fed it is, so trails lead to food.
+    /// The field then spreads and evaporates.
+    fn lay_pheromones(&mut self) {
+        if !self.pheromone.enabled() {
+            return;
+        }
+        if (self.pheromones.width, self.pheromones.height) != (self.width, self.height) {
+            self.pheromones = PheromoneField::new(self.width, self.height);
+        }
+        for group in 0..self.particles.group_count() {
+            for &slot in self.particles.members(group) {
+                let fed = self.particles.life_force[slot].max(0) as f32 / i8::MAX as f32;
+                self.pheromones.deposit(self.particles.x[slot], self.particles.y[slot], self.pheromone.deposit * fed);
+            }
+        }
+        self.pheromones.diffuse(self.pheromone.diffusion, self.pheromone.evaporation);
+        // Trails change every tick, even once the walkers are gone
+        self.needs_re_render = true;
+    }
+
+    /// Walkers move, eat from the tiles, burn life force and breed, dying once they run out
+    fn walker_lifecycle(&mut self) {
+        self.lay_pheromones();
+        if self.particles.len() == 0 {
+            return;
+        }
+        // Walkers move every tick, and the last ones to die need drawing over
+        self.needs_re_render = true;
+        self.walk();
+        self.harvest();
+        if self.metabolism.burns() {
+            self.particles.metabolise(&self.metabolism, 1.0);
+        }
+        self.breed();
+        // The dead leave what they ate on their tile
+        let (tiles, width, death_drop) = (&mut self.tiles, self.width, self.metabolism.death_drop);
+        self.particles.lifecycle(self.metabolism.max_age, |group, _, x, y| {
+            if let Some(tile) = Self::tile_at(tiles, width, x, y) {
+                tiles[tile].drop_resource(HABITATS[group].food(), death_drop);
+            }
+        });
+    }
+}
+
+impl Simulation for SimGrid {
+    fn name(&self) -> &'static str {
+        "tiles"
+    }
+
+    fn update(&mut self) {
         self.tile_lifecycle();
+        self.walker_lifecycle();
+        self.ticks += 1;
+        if cfg!(debug_assertions) && self.check_invariants {
+            self.assert_invariants();
+        }
+    }
+
+    fn draw(&mut self, canvas: &mut dyn Canvas) {
+        if !self.needs_re_render {
+            return;
+        }
+        self.render_screen(canvas);
+        if self.pheromone.enabled() && self.pheromone.overlay {
+            self.pheromones.draw_overlay(canvas);
+        }
+        self.draw_walkers(canvas);
     }
 
-    pub fn randomise(&mut self) {
-        println!("Randomising simulation grid...");
+    fn request_full_redraw(&mut self) {
+        self.needs_re_render = true;
+    }
+
+    fn randomise(&mut self) {
+        self.randomise_with_seed(generate_seed().0);
+    }
+
+    /// Generate the same tiles every time for a given seed
+    fn randomise_with_seed(&mut self, seed: u64) {
+        debug!("Randomising simulation grid with seed {}...", seed);
         // Clear Tiles
         self.tiles.clear();
+        self.seed = seed;
+        self.needs_re_render = true;
+
+        let mut rng = SimRng::seed_from_u64(seed);
 
-        let mut rng = rand::thread_rng();
-
-        // Generate tiles
-        println!("Tile calculations:");
-        let width_res_fit: i8 = (self.width / 32) as i8;
-        let height_res_fit: i8 = (self.height / 32) as i8;
-        println!("width_res_fit: {}", width_res_fit);
-        println!("height_res_fit: {}", height_res_fit);
-        let res_fit: i8 = width_res_fit * height_res_fit;
-        println!("res_fit: {}", res_fit);
-        for i in 0..res_fit {
+        // Generate tiles, only whole tiles that fit on screen are generated
+        let width_res_fit = self.width / TILE_SIZE;
+        let height_res_fit = self.height / TILE_SIZE;
+        debug!("Tile calculations: {}x{} tiles", width_res_fit, height_res_fit);
+        for i in 0..(width_res_fit * height_res_fit) {
             let random_terrain_type = match rng.gen_range(0..6) {
                 0 => 'f',
                 1 => 'w',
                 2 => 'm',
                 3 => 'h',
                 4 => 'p',
-                5 => 'w',
+                5 => 'o',
                 _ => '.',
             };
-            self.tiles.push(Tile::new(i as u8, i as u8, random_terrain_type));
+            self.tiles.push(Tile::new((i % width_res_fit) as u8, (i / width_res_fit) as u8, random_terrain_type, &mut rng));
         }
+        // Carry on from the terrain's generator, so walkers don't change the terrain a seed gives
+        self.rng = rng;
+        self.ticks = 0;
+        self.generate_walkers();
     }
 
-    fn tile_lifecycle(&mut self) {
-        for tile in self.tiles.iter_mut() {
-            tile.update_tile();
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn stats(&self) -> Vec<(&'static str, String)> {
+        let resources: i64 = self.tiles.iter().flat_map(|t| t.resources.iter()).map(|r| r.quantity as i64).sum();
+        let mut stats = vec![("tiles", self.tiles.len().to_string()), ("resources", resources.to_string()), ("walkers", self.particles.len().to_string())];
+        if self.pheromone.enabled() {
+            stats.push(("pheromone", format!("{:.0}", self.pheromones.total())));
+        }
+        stats
+    }
+
+    fn palette(&self) -> Vec<[u8; 4]> {
+        let mut palette = Tile::terrain_palette();
+        if self.pheromone.enabled() && self.pheromone.overlay {
+            palette.push(PHEROMONE_COLOUR);
+        }
+        palette.extend(HABITATS.iter().map(|habitat| {
+            let colour = habitat.colour();
+            [colour.r as u8, colour.g as u8, colour.b as u8, 255]
+        }));
+        palette
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let save = SimGridSave {
+            width: self.width,
+            height: self.height,
+            pixel_size: self.pixel_size,
+            seed: self.seed,
+            tiles: self.tiles.clone(),
+            walkers: if self.particles.len() == 0 {
+                vec![]
+            } else {
+                (0..self.particles.group_count()).map(|group| ParticleGroup::new(self.particles.colour(group), self.particles.group_particles(group))).collect()
+            },
+            metabolism: self.metabolism.clone(),
+            reproduction: self.reproduction.clone(),
+            global_id_count: self.global_id_count,
+            ticks: self.ticks,
+            pheromone: self.pheromone.clone(),
+            rng: Some(self.rng.clone()),
+            walkers_per_habitat: self.walkers_per_habitat,
+        };
+        serde_json::to_writer(writer, &save)?;
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let save: SimGridSave = serde_json::from_reader(reader)?;
+        if save.width == 0 || save.height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "saved grid has no area"));
+        }
+        save.metabolism.validate().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("saved metabolism: {err}")))?;
+        save.reproduction.validate().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("saved reproduction: {err}")))?;
+        if !save.walkers.is_empty() && save.walkers.len() != HABITATS.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("saved grid has {} walker groups, expected one per habitat", save.walkers.len())));
+        }
+        let mut grid = Self::new(save.width, save.height, save.pixel_size);
+        grid.check_invariants = self.check_invariants;
+        grid.seed = save.seed;
+        grid.tiles = save.tiles;
+        for tile in grid.tiles.iter_mut() {
+            tile.pixels = Tile::terrain_pixels(tile.terrain_type);
+        }
+        for (group, walkers) in save.walkers.iter().enumerate() {
+            for walker in walkers.group.iter() {
+                grid.particles.insert(group, walker);
+            }
+        }
+        grid.metabolism = save.metabolism;
+        grid.reproduction = save.reproduction;
+        // Saves from before walkers bred don't have it, but their walkers are numbered from 1
+        grid.global_id_count = save.global_id_count.max(grid.particles.len() as u32);
+        grid.ticks = save.ticks;
+        grid.pheromone = save.pheromone;
+        grid.walkers_per_habitat = save.walkers_per_habitat;
+        grid.rng = loaded_rng(save.rng, grid.seed, grid.ticks);
+        *self = grid;
+        Ok(())
+    }
+
+    fn set_check_invariants(&mut self, check_invariants: bool) {
+        self.check_invariants = check_invariants;
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if name == "walkers" {
+            let walkers_per_habitat = value.parse().map_err(|_| format!("expected a number of walkers per habitat, got {value}"))?;
+            // Keep the walkers there are, which may have been loaded, unless the count changes
+            if walkers_per_habitat != self.walkers_per_habitat {
+                self.walkers_per_habitat = walkers_per_habitat;
+                self.generate_walkers();
+                self.needs_re_render = true;
+            }
+            return Ok(());
+        }
+        if PheromoneConfig::OPTIONS.contains(&name) {
+            return self.pheromone.set(name, value);
+        }
+        if Reproduction::OPTIONS.contains(&name) {
+            return self.reproduction.set(name, value);
+        }
+        if !WALKER_METABOLISM_OPTIONS.contains(&name) {
+            return Err(format!("{} has no option {name}", self.name()));
         }
+        self.metabolism.set(name, value)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use crate::render::golden::{assert_golden, Tolerance};
+    use crate::components::resource::Resource;
+    use crate::render::image_buffer::ImageBuffer;
+
+    /// Counts how many times each pixel is written
+    struct CountingCanvas {
+        width: usize,
+        height: usize,
+        writes: Vec<u32>,
+    }
+
+    impl Canvas for CountingCanvas {
+        fn width(&self) -> usize {
+            self.width
+        }
+
+        fn height(&self) -> usize {
+            self.height
+        }
+
+        fn get_pixel(&self, _x: usize, _y: usize) -> [u8; 4] {
+            [0, 0, 0, 0]
+        }
+
+        fn set_pixel(&mut self, x: usize, y: usize, _colour: [u8; 4]) {
+            assert!(x < self.width && y < self.height, "wrote off screen at ({x}, {y})");
+            self.writes[y * self.width + x] += 1;
+        }
+    }
+
+    fn render_seeded(width: usize, height: usize, seed: u64) -> ImageBuffer {
+        let mut simulation = SimGrid::new(width, height, 1);
+        simulation.randomise_with_seed(seed);
+        let mut image = ImageBuffer::new(width, height);
+        simulation.render_screen(&mut image);
+        image
+    }
+
+    #[test]
+    fn seeded_world_matches_golden() {
+        assert_golden("sim_grid_250x250_seed_1", &render_seeded(250, 250, 1), Tolerance::default());
+    }
+
+    #[test]
+    fn non_square_world_matches_golden() {
+        // 5x3 tiles, catches mixing up the tile rows and columns
+        assert_golden("sim_grid_160x96_seed_7", &render_seeded(160, 96, 7), Tolerance::default());
+    }
+
+    #[test]
+    fn partial_tiles_are_left_empty() {
+        let image = render_seeded(70, 40, 3);
+        assert_eq!(image.get_pixel(69, 39), Tile::terrain_colour('.'));
+        assert_ne!(image.get_pixel(63, 31), Tile::terrain_colour('.'));
+    }
+
+    #[test]
+    fn walkers_eat_what_their_habitat_eats_from_their_tile() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut simulation = SimGrid::new(64, 32, 1);
+        simulation.tiles = vec![Tile::new(0, 0, 'f', &mut rng), Tile::new(1, 0, 'o', &mut rng)];
+        simulation.tiles[0].resources = vec![Resource { quantity: 5, ..Resource::new('f', [0, 0, 0, 255]) }];
+        simulation.tiles[1].resources = vec![Resource { quantity: 5, ..Resource::new('w', [0, 0, 0, 255]) }];
+        simulation.metabolism.set("harvest", "2").unwrap();
+        simulation.metabolism.set("food_energy", "5").unwrap();
+        let land = simulation.particles.insert(0, &Particle { x: 10.0, y: 10.0, life_force: 50, ..Particle::default() });
+        let water = simulation.particles.insert(1, &Particle { x: 40.0, y: 10.0, life_force: 50, ..Particle::default() });
+        simulation.harvest();
+        assert_eq!((simulation.particles.get(land).unwrap().life_force, simulation.tiles[0].resources[0].quantity), (60, 3));
+        // Fish would feed it, wood doesn't
+        assert_eq!((simulation.particles.get(water).unwrap().life_force, simulation.tiles[1].resources[0].quantity), (50, 5));
+        assert!(simulation.set_option("food_growth", "1").is_err());
+    }
+
+    #[test]
+    fn dead_walkers_leave_their_food_on_their_tile() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut simulation = SimGrid::new(64, 32, 1);
+        simulation.tiles = vec![Tile::new(0, 0, 'f', &mut rng), Tile::new(1, 0, 'o', &mut rng)];
+        simulation.tiles[1].resources = vec![Resource { quantity: 5, ..Resource::new('w', [0, 0, 0, 255]) }];
+        simulation.set_option("death_drop", "3").unwrap();
+        simulation.particles.insert(0, &Particle { x: 16.0, y: 16.0, life_force: 0, ..Particle::default() });
+        simulation.particles.insert(1, &Particle { x: 48.0, y: 16.0, life_force: 0, ..Particle::default() });
+        simulation.walker_lifecycle();
+        assert_eq!(simulation.particles.len(), 0);
+        assert_eq!(simulation.tiles[0].resources, vec![Resource { quantity: 3, ..Resource::new('f', [0, 0, 0, 255]) }]);
+        assert_eq!(simulation.tiles[1].resources[1], Resource { quantity: 3, ..Resource::new('p', [0, 0, 0, 255]) });
+    }
+
+    #[test]
+    fn setting_the_same_walker_count_keeps_loaded_walkers() {
+        let mut simulation = SimGrid::new(128, 96, 1);
+        simulation.randomise_with_seed(2);
+        simulation.set_option("walkers", "3").unwrap();
+        simulation.update();
+        let mut saved = vec![];
+        simulation.save(&mut saved).unwrap();
+        let mut loaded = SimGrid::new(1, 1, 1);
+        loaded.load(&mut saved.as_slice()).unwrap();
+        loaded.set_option("walkers", "3").unwrap();
+        assert_eq!(loaded.particles.x, simulation.particles.x);
+        loaded.set_option("walkers", "2").unwrap();
+        assert_eq!(loaded.particles.len(), 2 * HABITATS.len());
+    }
+
+    #[test]
+    fn rejected_saves_leave_the_grid_as_it_was() {
+        let mut simulation = SimGrid::new(128, 96, 1);
+        simulation.randomise_with_seed(4);
+        let mut saved = vec![];
+        simulation.save(&mut saved).unwrap();
+        let mut save: serde_json::Value = serde_json::from_slice(&saved).unwrap();
+        save["walkers"].as_array_mut().unwrap().pop();
+        assert!(simulation.load(&mut save.to_string().as_bytes()).is_err());
+        let mut after = vec![];
+        simulation.save(&mut after).unwrap();
+        assert!(after == saved);
+    }
+
+    #[test]
+    fn weak_walkers_are_drawn_faded() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut simulation = SimGrid::new(32, 32, 1);
+        simulation.tiles = vec![Tile::new(0, 0, 'f', &mut rng)];
+        simulation.particles.insert(0, &Particle { x: 4.0, y: 4.0, pixel_colour_rgba: [0, 0, 255, 255], life_force: 50, ..Particle::default() });
+        simulation.particles.insert(0, &Particle { x: 8.0, y: 4.0, pixel_colour_rgba: [0, 0, 255, 255], life_force: 100, ..Particle::default() });
+        let mut image = ImageBuffer::new(32, 32);
+        simulation.draw(&mut image);
+        assert_eq!(image.get_pixel(4, 4), [128, 0, 128, 255]);
+        assert_eq!(image.get_pixel(8, 4), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn walkers_breed_mutated_children_onto_their_habitat() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut simulation = SimGrid::new(64, 32, 1);
+        simulation.tiles = vec![Tile::new(0, 0, 'f', &mut rng), Tile::new(1, 0, 'o', &mut rng)];
+        for (name, value) in [("breed_at", "40"), ("birth_offset", "16"), ("mutation_rate", "1")] {
+            simulation.set_option(name, value).unwrap();
+        }
+        let parent = Particle { id: 1, x: 28.0, y: 16.0, life_force: 120, birth_rate: 4, preferences: vec![0.5; RESOURCE_TYPES.len()], ..Particle::default() };
+        simulation.particles.insert(0, &parent);
+        simulation.global_id_count = 1;
+        simulation.breed();
+        let children = simulation.particles.group_particles(0).split_off(1);
+        // Four born, those that landed in the ocean didn't survive
+        assert!(!children.is_empty() && children.len() < 4);
+        assert!(children.iter().all(|child| child.x < 32.0 && child.preferences != parent.preferences && child.lineage.parents[0] == Some(1)));
+        assert_eq!(simulation.global_id_count, 5);
+    }
+
+    #[test]
+    fn walkers_sense_the_way_to_what_they_prefer() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // A row of 4 tiles: food at either end, ocean on the right
+        let mut tiles: Vec<Tile> = "fpfo".chars().enumerate().map(|(i, terrain)| Tile::new(i as u8, 0, terrain, &mut rng)).collect();
+        tiles[0].resources = vec![Resource { quantity: 50, ..Resource::new('f', [0, 0, 0, 255]) }];
+        tiles[2].resources = vec![Resource { quantity: 5, ..Resource::new('f', [0, 0, 0, 255]) }];
+        tiles[3].resources = vec![Resource { quantity: 500, ..Resource::new('p', [0, 0, 0, 255]) }];
+        let mut preferences = [0.0; RESOURCE_TYPES.len()];
+        preferences[0] = 1.0;
+        let gradient = |sensing: f32, preferences: &[f32; RESOURCE_TYPES.len()]| SimGrid::resource_gradient(&tiles, 128, 32, Habitat::Land, 40.0, 10.0, sensing, preferences);
+        // The walker stands between the two food tiles, the bigger pile is on the left
+        assert_eq!(gradient(1.0, &preferences), (-1.0, 0.0));
+        assert_eq!(gradient(0.5, &preferences), (0.0, 0.0));
+        assert_eq!(gradient(1.0, &[0.0; RESOURCE_TYPES.len()]), (0.0, 0.0));
+        // Fish in the ocean it can't enter don't count, however much it would like them
+        preferences[5] = 1.0;
+        assert_eq!(gradient(2.0, &preferences), (-1.0, 0.0));
+        preferences[0] = -1.0;
+        assert_eq!(gradient(1.0, &preferences), (1.0, 0.0));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn every_tile_pixel_maps_to_one_screen_pixel(width in 1usize..300, height in 1usize..300, seed in any::<u64>()) {
+            let mut simulation = SimGrid::new(width, height, 1);
+            simulation.randomise_with_seed(seed);
+            let mut canvas = CountingCanvas { width, height, writes: vec![0; width * height] };
+            simulation.render_screen(&mut canvas);
+            // One write from clearing the screen, plus one from the tile covering it (if any)
+            let tiled_width = width / TILE_SIZE * TILE_SIZE;
+            let tiled_height = height / TILE_SIZE * TILE_SIZE;
+            for y in 0..height {
+                for x in 0..width {
+                    let expected = if x < tiled_width && y < tiled_height { 2 } else { 1 };
+                    prop_assert_eq!(canvas.writes[y * width + x], expected, "pixel ({}, {})", x, y);
+                }
+            }
+        }
+
+        #[test]
+        fn save_load_round_trips(seed in any::<u64>(), ticks in 0usize..20) {
+            let mut simulation = SimGrid::new(250, 250, 1);
+            simulation.randomise_with_seed(seed);
+            for _ in 0..ticks {
+                simulation.update();
+            }
+            let mut saved = vec![];
+            simulation.save(&mut saved).unwrap();
+            let mut loaded = SimGrid::new(1, 1, 1);
+            loaded.load(&mut saved.as_slice()).unwrap();
+            prop_assert_eq!((loaded.width, loaded.height, loaded.seed), (simulation.width, simulation.height, simulation.seed));
+            prop_assert_eq!(loaded.tiles, simulation.tiles);
+        }
+
+        #[test]
+        fn same_seed_generates_same_world(width in 32usize..300, height in 32usize..300, seed in any::<u64>()) {
+            let mut first = SimGrid::new(width, height, 1);
+            first.randomise_with_seed(seed);
+            let mut second = SimGrid::new(width, height, 1);
+            second.randomise_with_seed(seed);
+            prop_assert_eq!(first.tiles, second.tiles);
+        }
+
+        #[test]
+        fn invariants_hold_every_tick(seed in any::<u64>(), ticks in 1usize..10) {
+            let mut simulation = SimGrid::new(250, 250, 1);
+            simulation.check_invariants = true;
+            simulation.randomise_with_seed(seed);
+            for _ in 0..ticks {
+                simulation.update();
+            }
+        }
+    }
+}
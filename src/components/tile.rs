@@ -1,37 +1,32 @@
+use log::debug;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use crate::components::pixel::Pixel;
 use crate::components::resource::Resource;
 
-#[derive(Clone, PartialEq, Debug, Default)]
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Tile {
     pub x: u8,
     pub y: u8,
     pub terrain_type: char,
+    /// Not saved, rebuilt from the terrain type with `terrain_pixels` on load
+    #[serde(skip)]
     pub pixels: Vec<Pixel>,
     pub resources: Vec<Resource>,
     pub generates_resource: char,
 }
 
+/// Width and height of a tile in pixels
+pub const TILE_SIZE: usize = 32;
+
+/// Every terrain type `SimGrid::randomise` can generate
+pub const TERRAIN_TYPES: [char; 6] = ['f', 'w', 'm', 'h', 'p', 'o'];
+
 impl Tile {
-    pub fn new(x: u8, y: u8, terrain_type: char) -> Self {
+    pub fn new<R: Rng>(x: u8, y: u8, terrain_type: char, rng: &mut R) -> Self {
         // Setup pixels based on terrain type 
-        let mut temp_pixels = vec![];
-        // Create a 32x32 grid of pixels (resolution of a tile)
-        for _ in 0..(32*32) {
-            temp_pixels.push(Pixel::new(
-                // Return a colour based on the terrain type
-            match terrain_type {
-                'f' => [255, 0, 0, 255], // Red
-                'w' => [255, 125, 0, 255], // Orange
-                'm' => [100, 100, 100, 255], // Gray
-                'h' => [0, 255, 125, 255], // Teal
-                'p' => [0, 255, 0, 255], // Green
-                'w' => [0, 0, 255, 255], // Blue
-                _ => [0, 0, 0, 255], // Black (default for empty tiles)
-            }));
-        }
+        let temp_pixels = Self::terrain_pixels(terrain_type);
         // Possibly generate a resource dependant on terrain type and random chance
-        let mut rng = rand::thread_rng();
         let random_number: u8 = rng.gen_range(0..100);
         let mut generates_resource = 'n';
         match terrain_type {
@@ -51,23 +46,17 @@ impl Tile {
                     generates_resource = 's';
                 }
             },
-            'h' => {
+            'h' if random_number < 50 => {
                 // Generate stone or nothing
-                if random_number < 50 {
-                    generates_resource = 's';
-                }
+                generates_resource = 's';
             },
-            'p' => {
-                // Generate nothing
-                if random_number < 33 {
-                    generates_resource = 'h';
-                }
+            'p' if random_number < 33 => {
+                // Generate horses or nothing
+                generates_resource = 'h';
             },
-            'w' => {
-                // Generate a water
-                if random_number < 33 {
-                    generates_resource = 'p';
-                }
+            'o' if random_number < 33 => {
+                // Generate fish or nothing
+                generates_resource = 'p';
             },
             _ => {
                 // Do nothing
@@ -77,9 +66,56 @@ impl Tile {
         Self { x, y, terrain_type, pixels: temp_pixels, resources: vec![], generates_resource }
     }
 
+    pub fn terrain_pixels(terrain_type: char) -> Vec<Pixel> {
+        // Create a 32x32 grid of pixels (resolution of a tile)
+        vec![Pixel::new(Self::terrain_colour(terrain_type)); TILE_SIZE * TILE_SIZE]
+    }
+
+    /// Colour every pixel of a tile with the given terrain type is drawn in
+    pub fn terrain_colour(terrain_type: char) -> [u8; 4] {
+        match terrain_type {
+            'f' => [255, 0, 0, 255], // Red
+            'w' => [255, 125, 0, 255], // Orange
+            'm' => [100, 100, 100, 255], // Gray
+            'h' => [0, 255, 125, 255], // Teal
+            'p' => [0, 255, 0, 255], // Green
+            'o' => [0, 0, 255, 255], // Blue
+            _ => [0, 0, 0, 255], // Black (default for empty tiles)
+        }
+    }
+
+    /// Every colour a tile can be drawn in, including the empty tile colour
+    pub fn terrain_palette() -> Vec<[u8; 4]> {
+        let mut palette: Vec<[u8; 4]> = TERRAIN_TYPES.iter().map(|t| Self::terrain_colour(*t)).collect();
+        palette.push(Self::terrain_colour('.'));
+        palette
+    }
+
     pub fn update_tile(&mut self) {
         self.generate_resource();
-        println!("Tile Resources: {:?}", self.resources);
+        debug!("Tile Resources: {:?}", self.resources);
+    }
+
+    /// Add `quantity` of `resource_type` to the tile, whether or not it grows any itself
+    pub fn drop_resource(&mut self, resource_type: char, quantity: i16) {
+        if quantity <= 0 {
+            return;
+        }
+        match self.resources.iter_mut().find(|r| r.resource_type == resource_type) {
+            Some(resource) => resource.add_resource(quantity),
+            None => self.resources.push(Resource { quantity, ..Resource::new(resource_type, [0, 0, 0, 255]) }),
+        }
+    }
+
+    pub fn assert_invariants(&self) {
+        assert_eq!(self.pixels.len(), TILE_SIZE * TILE_SIZE, "tile ({}, {}) has the wrong number of pixels", self.x, self.y);
+        for (i, r) in self.resources.iter().enumerate() {
+            r.assert_invariants();
+            assert!(
+                self.resources[..i].iter().all(|other| other.resource_type != r.resource_type),
+                "tile ({}, {}) holds resource {} more than once", self.x, self.y, r.resource_type,
+            );
+        }
     }
 
     fn generate_resource(&mut self) {
@@ -120,4 +156,49 @@ impl Tile {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn terrain_type() -> impl Strategy<Value = char> {
+        prop::sample::select(TERRAIN_TYPES.to_vec())
+    }
+
+    proptest! {
+        #[test]
+        fn new_tiles_are_filled_with_their_terrain_colour(terrain in terrain_type(), seed in any::<u64>()) {
+            let tile = Tile::new(3, 4, terrain, &mut StdRng::seed_from_u64(seed));
+            tile.assert_invariants();
+            prop_assert!(tile.pixels.iter().all(|p| p.colour_rgba == Tile::terrain_colour(terrain)));
+            prop_assert!(Tile::terrain_palette().contains(&Tile::terrain_colour(terrain)));
+        }
+
+        #[test]
+        fn updates_keep_one_growing_stock_per_resource(terrain in terrain_type(), seed in any::<u64>(), ticks in 0usize..300) {
+            let mut tile = Tile::new(0, 0, terrain, &mut StdRng::seed_from_u64(seed));
+            for _ in 0..ticks {
+                tile.update_tile();
+            }
+            tile.assert_invariants();
+            match tile.generates_resource {
+                'n' => prop_assert!(tile.resources.is_empty()),
+                _ if ticks > 0 => prop_assert_eq!(tile.resources[0].quantity as usize, ticks),
+                _ => prop_assert!(tile.resources.is_empty()),
+            }
+        }
+    }
+
+    #[test]
+    fn resources_saturate_instead_of_overflowing() {
+        let mut tile = Tile::new(0, 0, 'f', &mut StdRng::seed_from_u64(0));
+        tile.update_tile();
+        tile.resources[0].quantity = i16::MAX;
+        tile.update_tile();
+        assert_eq!(tile.resources[0].quantity, i16::MAX);
+    }
+}
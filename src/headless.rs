@@ -0,0 +1,189 @@
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+use crate::components::rule_preset::RulePreset;
+use crate::recorder::{GifConfig, GifRecorder};
+use crate::render::image_buffer::ImageBuffer;
+use crate::simulation::{self, SimulationSettings, SIMULATIONS};
+use crate::{HEIGHT, WIDTH};
+
+pub const USAGE: &str = "usage: life_simulator --headless [--mode NAME] [--ticks N] [--seed N] [--load PATH] [--save PATH] [--check-invariants] [--preset NAME] [--save-preset PATH] [--set NAME=VALUE]... [--threads N] [--gif PATH] [--gif-interval TICKS] [--gif-downscale N] [--gif-delay CENTISECONDS] [--phylogeny PATH.dot|PATH.nwk] [--prune-extinct]";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct HeadlessConfig {
+    /// Name of the simulation to generate, see `simulation::SIMULATIONS`
+    pub mode: &'static str,
+    pub ticks: u64,
+    /// World seed, a random one is used when not given
+    pub seed: Option<u64>,
+    /// Start from a saved simulation instead of generating one
+    pub load_path: Option<PathBuf>,
+    /// Save the simulation once all ticks have run
+    pub save_path: Option<PathBuf>,
+    /// Save the rules as a preset once all ticks have run
+    pub save_preset_path: Option<PathBuf>,
+    pub settings: SimulationSettings,
+    pub gif_path: Option<PathBuf>,
+    pub gif: GifConfig,
+    /// Export the run's ancestry once all ticks have run, in the format the extension names
+    pub phylogeny_path: Option<PathBuf>,
+    /// Leave particles without living descendants out of the ancestry export
+    pub prune_extinct: bool,
+}
+
+/// File formats `--phylogeny` can write
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PhylogenyFormat {
+    /// Graphviz
+    Dot,
+    Newick,
+}
+
+impl PhylogenyFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("dot" | "gv") => Ok(Self::Dot),
+            Some("nwk" | "newick" | "tree") => Ok(Self::Newick),
+            _ => Err(format!("can't tell the format of {} for --phylogeny, expected a .dot, .gv, .nwk, .newick or .tree file", path.display())),
+        }
+    }
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        Self { mode: SIMULATIONS[0].name, ticks: 600, seed: None, load_path: None, save_path: None, save_preset_path: None, settings: SimulationSettings::default(), gif_path: None, gif: GifConfig::default(), phylogeny_path: None, prune_extinct: false }
+    }
+}
+
+impl HeadlessConfig {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+            match arg.as_str() {
+                "--headless" => {},
+                "--mode" => {
+                    let name = value()?;
+                    config.mode = SIMULATIONS.iter().map(|kind| kind.name).find(|kind| *kind == name).ok_or(format!(
+                        "unknown mode for {arg}, expected one of: {}",
+                        simulation::simulation_names().join(", ")
+                    ))?;
+                },
+                "--ticks" => config.ticks = parse_number(arg, value()?)?,
+                "--seed" => config.seed = Some(parse_number(arg, value()?)?),
+                "--load" => config.load_path = Some(PathBuf::from(value()?)),
+                "--save" => config.save_path = Some(PathBuf::from(value()?)),
+                "--save-preset" => config.save_preset_path = Some(PathBuf::from(value()?)),
+                "--preset" => config.settings.preset = Some(RulePreset::find(value()?)?),
+                "--check-invariants" => config.settings.check_invariants = true,
+                "--set" => config.settings.options.push(SimulationSettings::parse_option(value()?)?),
+                // Main sets up the thread pool before parsing the rest
+                "--threads" => {
+                    parse_number::<usize>(arg, value()?)?;
+                },
+                "--gif" => config.gif_path = Some(PathBuf::from(value()?)),
+                "--gif-interval" => config.gif.frame_interval = parse_number(arg, value()?)?,
+                "--gif-downscale" => config.gif.downscale = parse_number(arg, value()?)?,
+                "--gif-delay" => config.gif.frame_delay = parse_number(arg, value()?)?,
+                "--phylogeny" => {
+                    let path = PathBuf::from(value()?);
+                    PhylogenyFormat::from_path(&path)?;
+                    config.phylogeny_path = Some(path);
+                },
+                "--prune-extinct" => config.prune_extinct = true,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if config.load_path.is_some() && (config.settings.preset.is_some() || !config.settings.options.is_empty()) {
+            return Err(String::from("--preset and --set can't be combined with --load, the save has its own settings"));
+        }
+        if config.gif.frame_interval == 0 || config.gif.downscale == 0 {
+            return Err(String::from("--gif-interval and --gif-downscale must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value for {arg}: {value}"))
+}
+
+/// Run the simulation without a window, optionally recording it to a GIF
+pub fn run(config: &HeadlessConfig) -> io::Result<()> {
+    let (width, height) = (WIDTH as usize, HEIGHT as usize);
+    let mut simulation = match &config.load_path {
+        Some(path) => {
+            let mut simulation = simulation::load_file(path, width, height)?;
+            config.settings.apply_to_loaded(simulation.as_mut());
+            simulation
+        },
+        None => {
+            let mut simulation = simulation::create(config.mode, width, height).expect("mode was validated by from_args");
+            if let Some(seed) = config.seed {
+                simulation.randomise_with_seed(seed);
+            }
+            config.settings.apply(simulation.as_mut()).map_err(|message| io::Error::new(io::ErrorKind::InvalidInput, message))?;
+            simulation
+        },
+    };
+    info!("World seed: {}", simulation.seed());
+
+    let mut recorder = match &config.gif_path {
+        Some(path) => Some(GifRecorder::create(path, width, height, simulation.palette(), config.gif)?),
+        None => None,
+    };
+    let mut image = ImageBuffer::new(width, height);
+
+    for tick in 0..config.ticks {
+        simulation.update();
+        if let Some(recorder) = recorder.as_mut() {
+            if recorder.wants_frame(tick) {
+                simulation.draw(&mut image);
+                recorder.write_frame(&image)?;
+            }
+        }
+    }
+
+    info!("Ran {} ticks headless, ended with {}", config.ticks, simulation::status_line(simulation.as_ref()));
+    if let Some(path) = &config.save_path {
+        simulation::save_file(simulation.as_ref(), path)?;
+        info!("Saved simulation to {}", path.display());
+    }
+    if let Some(path) = &config.phylogeny_path {
+        let phylogeny = simulation.phylogeny().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} doesn't record ancestry", simulation.name())))?;
+        let format = PhylogenyFormat::from_path(path).expect("format was validated by from_args");
+        fs::write(
+            path,
+            match format {
+                PhylogenyFormat::Dot => phylogeny.to_dot(config.prune_extinct),
+                PhylogenyFormat::Newick => phylogeny.to_newick(config.prune_extinct),
+            },
+        )?;
+        let summary = phylogeny.summary();
+        let describe = |found: Option<(u32, String)>| found.map_or(String::from("none"), |(id, value)| format!("{id} ({value})"));
+        info!(
+            "Wrote phylogeny to {}: {} births, {} living in {} lineages, longest-lived lineage {}, most prolific ancestor {}",
+            path.display(),
+            summary.births,
+            summary.living,
+            summary.living_lineages,
+            describe(summary.longest_lived_lineage.map(|(id, ticks)| (id, format!("{ticks} ticks")))),
+            describe(summary.most_prolific_ancestor.map(|(id, children)| (id, format!("{children} children")))),
+        );
+    }
+    if let Some(path) = &config.save_preset_path {
+        let preset = simulation.rule_preset().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no rules to save as a preset", simulation.name())))?;
+        preset.save(path)?;
+        info!("Saved preset to {}", path.display());
+    }
+    if let (Some(recorder), Some(path)) = (recorder, &config.gif_path) {
+        let frames_written = recorder.frames_written;
+        recorder.finish()?;
+        info!("Wrote {frames_written} GIF frames to {}", path.display());
+    }
+    Ok(())
+}
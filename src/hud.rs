@@ -0,0 +1,22 @@
+use crate::render::font::{GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::render::Canvas;
+use crate::simulation::Simulation;
+
+const TEXT_COLOUR: [u8; 4] = [255, 255, 255, 255];
+const BACKING_COLOUR: [u8; 4] = [0, 0, 0, 255];
+
+/// Draw the simulation's stats in the top left corner.
+/// The text sits on an opaque backing so it can be redrawn over models that don't clear the canvas.
+pub fn draw_hud(canvas: &mut dyn Canvas, simulation: &dyn Simulation, tick: u64, paused: bool) {
+    let mut lines = vec![format!("{} tick {tick}{}", simulation.name(), if paused { " paused" } else { "" })];
+    for (label, value) in simulation.stats() {
+        lines.push(format!("{label}: {value}"));
+    }
+
+    let line_height = GLYPH_HEIGHT + 1;
+    let longest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    canvas.fill_rect(0, 0, longest * (GLYPH_WIDTH + 1) + 3, lines.len() * line_height + 3, BACKING_COLOUR);
+    for (i, line) in lines.iter().enumerate() {
+        canvas.draw_text(2, 2 + i * line_height, line, TEXT_COLOUR, 1);
+    }
+}
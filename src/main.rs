@@ -1,16 +1,28 @@
 #![deny(clippy::all)]
 #![forbid(unsafe_code)]
 
+#[cfg(test)]
+mod bench;
 mod components;
-// use components::life_grid::LifeGrid;
-// use components::particle::Particle;
-// use components::particle_group::ParticleGroup;
-// use components::rule::Rule;
+mod headless;
+mod hud;
+mod matrix_editor;
+mod recorder;
+mod render;
+mod simulation;
+mod spatial;
+mod terminal;
 
-use components::simulation_grid::SimGrid;
+use components::rule_preset::RulePreset;
+use headless::HeadlessConfig;
+use matrix_editor::MatrixLayout;
+use simulation::{Simulation, SimulationSettings};
+use render::FrameCanvas;
+
+use std::path::Path;
 
 use error_iter::ErrorIter as _;
-use log::{error, log};
+use log::{error, info, warn};
 use pixels::{Error, Pixels, SurfaceTexture};
 use winit::{
     dpi::LogicalSize,
@@ -22,11 +34,91 @@ use winit_input_helper::WinitInputHelper;
 
 const WIDTH: u32 = 250;
 const HEIGHT: u32 = 250;
+/// Where the S and L keys save and load the simulation
+const SAVE_PATH: &str = "life_simulator_save.json";
 const PARTICLE_GROUPS_TO_GENERATE: usize = 8;
 const MAX_PARTICLES_PER_GROUP: usize = 1000;
+/// Values of the particle world's `render` option the B key cycles through
+const PARTICLE_RENDERINGS: [&str; 3] = ["overwrite", "blend", "heatmap"];
 
 fn main() -> Result<(), Error> {
     env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // Defaults to one thread per core, 1 runs everything on one worker for debugging
+    if let Some(i) = args.iter().position(|arg| arg == "--threads") {
+        let threads = match args.get(i + 1).and_then(|value| value.parse::<usize>().ok()) {
+            Some(threads) if threads > 0 => threads,
+            _ => {
+                error!("--threads must be a number of at least 1");
+                std::process::exit(2);
+            }
+        };
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global().expect("the thread pool is only set up once");
+    }
+    if args.iter().any(|arg| arg == "--headless") {
+        let config = match HeadlessConfig::from_args(&args) {
+            Ok(config) => config,
+            Err(message) => {
+                error!("{message}");
+                error!("{}", headless::USAGE);
+                std::process::exit(2);
+            }
+        };
+        if let Err(err) = headless::run(&config) {
+            log_error("headless::run", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let mode = match args.iter().position(|arg| arg == "--mode") {
+        Some(i) => match args.get(i + 1).and_then(|name| simulation::simulation_names().into_iter().find(|n| *n == name.as_str())) {
+            Some(mode) => mode,
+            None => {
+                error!("--mode must be one of: {}", simulation::simulation_names().join(", "));
+                std::process::exit(2);
+            }
+        },
+        None => simulation::SIMULATIONS[0].name,
+    };
+    let mut settings = SimulationSettings {
+        // Only has an effect in debug builds
+        check_invariants: args.iter().any(|arg| arg == "--check-invariants"),
+        preset: None,
+        options: vec![],
+    };
+    if let Some(i) = args.iter().position(|arg| arg == "--preset") {
+        match args.get(i + 1).ok_or(String::from("missing value for --preset")).and_then(|name| RulePreset::find(name)) {
+            Ok(preset) => settings.preset = Some(preset),
+            Err(message) => {
+                error!("{message}");
+                error!("built in presets: {}", RulePreset::built_in_names().join(", "));
+                std::process::exit(2);
+            }
+        }
+    }
+    for pair in args.windows(2).filter(|pair| pair[0] == "--set") {
+        match SimulationSettings::parse_option(&pair[1]) {
+            Ok(option) => settings.options.push(option),
+            Err(message) => {
+                error!("{message}");
+                std::process::exit(2);
+            }
+        }
+    }
+    let mut simulation = simulation::create(mode, WIDTH as usize, HEIGHT as usize).expect("mode was checked above");
+    if let Err(message) = settings.apply(simulation.as_mut()) {
+        error!("{message}");
+        std::process::exit(2);
+    }
+    if args.iter().any(|arg| arg == "--terminal") {
+        if let Err(err) = terminal::run(simulation, &settings) {
+            log_error("terminal::run", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
 
@@ -50,15 +142,27 @@ fn main() -> Result<(), Error> {
     // Customises the background colour
     // pixels.clear_color(Color::BLACK);
 
-    let mut simulation = SimGrid::new(WIDTH as usize, HEIGHT as usize, 1);
-    // let mut life = LifeGrid::new_random(WIDTH as usize, HEIGHT as usize, PARTICLE_GROUPS_TO_GENERATE as usize);
     let mut paused = false;
+    let mut show_hud = true;
+    let mut show_matrix = false;
+    let mut colour_by_genotype = false;
+    // Index into `PARTICLE_RENDERINGS`
+    let mut rendering = 0;
+    // Rule matrix cell under the cursor
+    let mut hovered: Option<(usize, usize)> = None;
+    let mut tick: u64 = 0;
 
     event_loop.run(move |event, _, control_flow| {
         // The one and only event that winit_input_helper doesn't have for us...
         if let Event::RedrawRequested(_) = event {
-            simulation.draw(pixels.frame_mut());
-            // life.draw(pixels.frame_mut());
+            let mut canvas = FrameCanvas::new(pixels.frame_mut(), WIDTH as usize, HEIGHT as usize);
+            simulation.draw(&mut canvas);
+            if show_hud {
+                hud::draw_hud(&mut canvas, simulation.as_ref(), tick, paused);
+            }
+            if let (true, Some(preset)) = (show_matrix, simulation.rule_preset()) {
+                MatrixLayout::new(WIDTH as usize, preset.groups).draw(&mut canvas, &preset, hovered);
+            }
             if let Err(err) = pixels.render() {
                 log_error("pixels.render", err);
                 *control_flow = ControlFlow::Exit;
@@ -83,7 +187,62 @@ fn main() -> Result<(), Error> {
             }
             if input.key_pressed(VirtualKeyCode::R) {
                 simulation.randomise();
-                simulation.needsReRender = true;
+            }
+            if input.key_pressed(VirtualKeyCode::M) {
+                simulation = create_simulation(simulation::next_name(simulation.name()), &settings);
+                tick = 0;
+                info!("Switched to {}", simulation::status_line(simulation.as_ref()));
+            }
+            if input.key_pressed(VirtualKeyCode::H) {
+                show_hud = !show_hud;
+                // Clear away the old HUD
+                simulation.request_full_redraw();
+            }
+            if input.key_pressed(VirtualKeyCode::E) {
+                show_matrix = !show_matrix;
+                simulation.request_full_redraw();
+            }
+            if input.key_pressed(VirtualKeyCode::G) {
+                colour_by_genotype = !colour_by_genotype;
+                if let Err(message) = simulation.set_option("colour_by", if colour_by_genotype { "genotype" } else { "group" }) {
+                    warn!("{message}");
+                }
+            }
+            if input.key_pressed(VirtualKeyCode::B) {
+                rendering = (rendering + 1) % PARTICLE_RENDERINGS.len();
+                if let Err(message) = simulation.set_option("render", PARTICLE_RENDERINGS[rendering]) {
+                    warn!("{message}");
+                }
+            }
+            if input.key_pressed(VirtualKeyCode::N) {
+                load_next_preset(simulation.as_mut());
+            }
+            if input.key_pressed(VirtualKeyCode::W) {
+                save_preset(simulation.as_ref());
+            }
+            if let (true, Some(mut preset)) = (show_matrix, simulation.rule_preset()) {
+                let layout = MatrixLayout::new(WIDTH as usize, preset.groups);
+                hovered = input.mouse().and_then(|position| pixels.window_pos_to_pixel(position).ok()).and_then(|(x, y)| layout.cell_at(x, y));
+                let steps = if input.mouse_pressed(0) { 1.0 } else if input.mouse_pressed(1) { -1.0 } else { 0.0 };
+                if let (Some(cell), true) = (hovered, steps != 0.0) {
+                    // Edits of a built in preset are saved under their own name
+                    if RulePreset::built_in_names().contains(&preset.name.as_str()) {
+                        preset.name.push_str("-custom");
+                    }
+                    matrix_editor::adjust(&mut preset, cell, steps);
+                    if let Err(message) = simulation.apply_preset(&preset) {
+                        warn!("{message}");
+                    }
+                }
+            }
+            if input.key_pressed(VirtualKeyCode::S) {
+                save_simulation(simulation.as_ref());
+            }
+            if input.key_pressed(VirtualKeyCode::L) {
+                if let Some(loaded) = load_simulation(&settings) {
+                    simulation = loaded;
+                    tick = 0;
+                }
             }
 
             // Resize the window
@@ -96,12 +255,67 @@ fn main() -> Result<(), Error> {
             }
             if !paused || input.key_pressed_os(VirtualKeyCode::Space) {
                 simulation.update();
+                tick += 1;
             }
             window.request_redraw();
         }
     });
 }
 
+/// Create `mode` with `settings` applied, logging any options it doesn't accept
+fn create_simulation(mode: &str, settings: &SimulationSettings) -> Box<dyn Simulation> {
+    let mut simulation = simulation::create(mode, WIDTH as usize, HEIGHT as usize).expect("unknown simulation mode");
+    if let Err(message) = settings.apply(simulation.as_mut()) {
+        warn!("{message}");
+    }
+    simulation
+}
+
+fn save_simulation(simulation: &dyn Simulation) {
+    match simulation::save_file(simulation, Path::new(SAVE_PATH)) {
+        Ok(()) => info!("Saved simulation to {SAVE_PATH}"),
+        Err(err) => log_error("simulation::save_file", err),
+    }
+}
+
+fn load_simulation(settings: &SimulationSettings) -> Option<Box<dyn Simulation>> {
+    match simulation::load_file(Path::new(SAVE_PATH), WIDTH as usize, HEIGHT as usize) {
+        Ok(mut simulation) => {
+            info!("Loaded simulation from {SAVE_PATH}");
+            settings.apply_to_loaded(simulation.as_mut());
+            Some(simulation)
+        },
+        Err(err) => {
+            log_error("simulation::load_file", err);
+            None
+        },
+    }
+}
+
+/// Switch to the preset after the current one, see `RulePreset::available_names`
+fn load_next_preset(simulation: &mut dyn Simulation) {
+    let Some(current) = simulation.rule_preset() else {
+        return;
+    };
+    let names = RulePreset::available_names();
+    let next = names.iter().position(|name| *name == current.name).map_or(0, |i| (i + 1) % names.len());
+    match RulePreset::find(&names[next]).and_then(|preset| simulation.apply_preset(&preset)) {
+        Ok(()) => info!("Loaded preset {}", names[next]),
+        Err(message) => warn!("{message}"),
+    }
+}
+
+fn save_preset(simulation: &dyn Simulation) {
+    let Some(preset) = simulation.rule_preset() else {
+        return;
+    };
+    let path = RulePreset::path_for(&preset.name);
+    match preset.save(&path) {
+        Ok(()) => info!("Saved preset to {}", path.display()),
+        Err(err) => log_error("RulePreset::save", err),
+    }
+}
+
 fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
     error!("{method_name}() failed: {err}");
     for source in err.sources().skip(1) {
@@ -0,0 +1,124 @@
+use crate::components::rule_preset::RulePreset;
+use crate::render::font::{GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::render::Canvas;
+
+const CELL_SIZE: usize = 10;
+/// Characters the panel always has room for below the matrix
+const LABEL_LENGTH: usize = 10;
+/// How much a click changes a rule's strength
+pub const STEP: f32 = 0.1;
+const TEXT_COLOUR: [u8; 4] = [255, 255, 255, 255];
+const BACKING_COLOUR: [u8; 4] = [0, 0, 0, 255];
+
+/// Grid of the rule strengths between every pair of groups, in the top right corner of the canvas.
+/// Row `one` column `two` is the rule moving group `one` relative to group `two`, with the group
+/// colours along the top and left. Red pushes away and green pulls closer, brighter when stronger.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MatrixLayout {
+    /// Top left corner of the first rule's cell
+    x: usize,
+    y: usize,
+    groups: usize,
+}
+
+impl MatrixLayout {
+    pub fn new(canvas_width: usize, groups: usize) -> Self {
+        let x = canvas_width.saturating_sub(groups * CELL_SIZE + 2);
+        Self { x, y: CELL_SIZE + 2, groups }
+    }
+
+    /// The (one, two) rule whose cell holds canvas position (`x`, `y`)
+    pub fn cell_at(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let (column, row) = (x.checked_sub(self.x)? / CELL_SIZE, y.checked_sub(self.y)? / CELL_SIZE);
+        (row < self.groups && column < self.groups).then_some((row, column))
+    }
+
+    pub fn draw(&self, canvas: &mut dyn Canvas, preset: &RulePreset, hovered: Option<(usize, usize)>) {
+        let size = self.groups * CELL_SIZE;
+        let line_height = GLYPH_HEIGHT + 1;
+        let right = self.x + size + 2;
+        let left = self.x.saturating_sub(CELL_SIZE + 2).min(right.saturating_sub(LABEL_LENGTH * (GLYPH_WIDTH + 1) + 2));
+        canvas.fill_rect(left, 0, right - left, size + CELL_SIZE + 2 + 2 * line_height + 1, BACKING_COLOUR);
+        for group in 0..self.groups {
+            let colour = preset.colours.get(group).map_or(TEXT_COLOUR, |&[r, g, b]| [r, g, b, 255]);
+            canvas.fill_rect(self.x + group * CELL_SIZE + 1, 1, CELL_SIZE - 2, CELL_SIZE - 2, colour);
+            canvas.fill_rect(self.x.saturating_sub(CELL_SIZE), self.y + group * CELL_SIZE + 1, CELL_SIZE - 2, CELL_SIZE - 2, colour);
+        }
+        for one in 0..self.groups {
+            for two in 0..self.groups {
+                let (x, y) = (self.x + two * CELL_SIZE, self.y + one * CELL_SIZE);
+                if hovered == Some((one, two)) {
+                    canvas.fill_rect(x, y, CELL_SIZE, CELL_SIZE, TEXT_COLOUR);
+                }
+                canvas.fill_rect(x + 1, y + 1, CELL_SIZE - 2, CELL_SIZE - 2, strength_colour(preset.g(one, two)));
+            }
+        }
+
+        let text_y = self.y + size + 2;
+        let fits = (right - left - 2) / (GLYPH_WIDTH + 1);
+        let name: String = preset.name.chars().take(fits).collect();
+        canvas.draw_text(left + 2, text_y, &name, TEXT_COLOUR, 1);
+        if let Some((one, two)) = hovered {
+            let label = format!("{one}-{two} {:.2}", preset.g(one, two));
+            canvas.draw_text(left + 2, text_y + line_height, &label, TEXT_COLOUR, 1);
+        }
+    }
+}
+
+fn strength_colour(g: f32) -> [u8; 4] {
+    // Grey for no rule, so empty cells still show
+    let brightness = 32 + (g.abs().min(1.0) * 223.0) as u8;
+    if g > 0.0 {
+        [brightness, 0, 0, 255]
+    } else if g < 0.0 {
+        [0, brightness, 0, 255]
+    } else {
+        [32, 32, 32, 255]
+    }
+}
+
+/// Change the strength of the (`one`, `two`) rule by `steps` clicks, keeping it within -1 to 1
+pub fn adjust(preset: &mut RulePreset, (one, two): (usize, usize), steps: f32) {
+    let g = ((preset.g(one, two) + steps * STEP) / STEP).round() * STEP;
+    preset.set_g(one, two, g.clamp(-1.0, 1.0));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::golden::{assert_golden, Tolerance};
+    use crate::render::image_buffer::ImageBuffer;
+
+    #[test]
+    fn draws_the_orbits_preset() {
+        let preset = RulePreset::find("orbits").unwrap();
+        let mut image = ImageBuffer::new(120, 60);
+        MatrixLayout::new(120, preset.groups).draw(&mut image, &preset, Some((1, 2)));
+        assert_golden("matrix_editor_orbits", &image, Tolerance::default());
+    }
+
+    #[test]
+    fn cells_map_back_to_their_rules() {
+        let layout = MatrixLayout::new(250, 8);
+        for one in 0..8 {
+            for two in 0..8 {
+                let (x, y) = (layout.x + two * CELL_SIZE + CELL_SIZE / 2, layout.y + one * CELL_SIZE + CELL_SIZE / 2);
+                assert_eq!(layout.cell_at(x, y), Some((one, two)));
+            }
+        }
+        assert_eq!(layout.cell_at(layout.x - 1, layout.y), None);
+        assert_eq!(layout.cell_at(layout.x, layout.y + 8 * CELL_SIZE), None);
+    }
+
+    #[test]
+    fn adjusting_snaps_to_steps_and_stays_in_range() {
+        let mut preset = RulePreset::find("cells").unwrap();
+        preset.set_g(0, 1, 0.33);
+        adjust(&mut preset, (0, 1), 1.0);
+        assert!((preset.g(0, 1) - 0.4).abs() < 1e-6);
+        for _ in 0..20 {
+            adjust(&mut preset, (0, 1), -1.0);
+        }
+        assert_eq!(preset.g(0, 1), -1.0);
+    }
+}
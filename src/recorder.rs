@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use gif::{Encoder, Frame, Repeat};
+
+use crate::render::image_buffer::ImageBuffer;
+use crate::render::Canvas;
+
+/// Most colours `GifRecorder` remembers the nearest palette entry of, past this it starts over
+const MAX_CACHED_COLOURS: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GifConfig {
+    /// Simulation ticks between captured frames
+    pub frame_interval: u32,
+    /// Each side of the output is divided by this (box filtered)
+    pub downscale: u32,
+    /// Delay between frames in hundredths of a second
+    pub frame_delay: u16,
+}
+
+impl Default for GifConfig {
+    fn default() -> Self {
+        Self { frame_interval: 10, downscale: 1, frame_delay: 10 }
+    }
+}
+
+/// Streams RGBA frames into an animated GIF, one frame at a time.
+/// Only the current frame's palette indices are held in memory, so long runs don't grow.
+pub struct GifRecorder<W: Write> {
+    encoder: Encoder<W>,
+    config: GifConfig,
+    width: usize,
+    height: usize,
+    out_width: usize,
+    out_height: usize,
+    palette: Vec<[u8; 4]>,
+    /// Nearest palette entry for colours seen recently, up to `MAX_CACHED_COLOURS`
+    palette_cache: HashMap<[u8; 3], u8>,
+    indices: Vec<u8>,
+    pub frames_written: u32,
+}
+
+impl GifRecorder<BufWriter<File>> {
+    pub fn create(path: &Path, width: usize, height: usize, palette: Vec<[u8; 4]>, config: GifConfig) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Self::new(file, width, height, palette, config)
+    }
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(writer: W, width: usize, height: usize, palette: Vec<[u8; 4]>, config: GifConfig) -> io::Result<Self> {
+        assert!(config.frame_interval != 0 && config.downscale != 0);
+        assert!(!palette.is_empty() && palette.len() <= 256);
+        let out_width = (width / config.downscale as usize).max(1);
+        let out_height = (height / config.downscale as usize).max(1);
+        if out_width > u16::MAX as usize || out_height > u16::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "GIF dimensions must fit in 16 bits"));
+        }
+
+        let mut global_palette: Vec<u8> = Vec::with_capacity(palette.len() * 3);
+        for colour in palette.iter() {
+            global_palette.extend_from_slice(&colour[..3]);
+        }
+        let mut encoder = Encoder::new(writer, out_width as u16, out_height as u16, &global_palette).map_err(io::Error::other)?;
+        encoder.set_repeat(Repeat::Infinite).map_err(io::Error::other)?;
+
+        Ok(Self {
+            encoder,
+            config,
+            width,
+            height,
+            out_width,
+            out_height,
+            palette,
+            palette_cache: HashMap::new(),
+            indices: vec![0; out_width * out_height],
+            frames_written: 0,
+        })
+    }
+
+    /// Whether the frame after `tick` should be captured
+    pub fn wants_frame(&self, tick: u64) -> bool {
+        tick.is_multiple_of(self.config.frame_interval as u64)
+    }
+
+    /// Downscale, quantise and encode a frame the size the recorder was created with
+    pub fn write_frame(&mut self, image: &ImageBuffer) -> io::Result<()> {
+        assert!(image.width == self.width && image.height == self.height);
+        let scale = self.config.downscale as usize;
+        for out_y in 0..self.out_height {
+            for out_x in 0..self.out_width {
+                // Average the block of source pixels this output pixel covers, only the part
+                // inside the image when it's smaller than a block
+                let xs = out_x * scale..((out_x + 1) * scale).min(self.width);
+                let ys = out_y * scale..((out_y + 1) * scale).min(self.height);
+                let mut sum = [0u32; 3];
+                for y in ys.clone() {
+                    for x in xs.clone() {
+                        for (s, channel) in sum.iter_mut().zip(image.get_pixel(x, y)) {
+                            *s += channel as u32;
+                        }
+                    }
+                }
+                let count = (xs.len() * ys.len()) as u32;
+                let colour = [(sum[0] / count) as u8, (sum[1] / count) as u8, (sum[2] / count) as u8];
+                self.indices[out_y * self.out_width + out_x] = self.palette_index(colour);
+            }
+        }
+
+        let frame = Frame {
+            width: self.out_width as u16,
+            height: self.out_height as u16,
+            delay: self.config.frame_delay,
+            buffer: self.indices.as_slice().into(),
+            ..Frame::default()
+        };
+        self.encoder.write_frame(&frame).map_err(io::Error::other)?;
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    /// Write the GIF trailer and flush the underlying writer
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = self.encoder.into_inner()?;
+        writer.flush()?;
+        Ok(writer)
+    }
+
+    fn palette_index(&mut self, colour: [u8; 3]) -> u8 {
+        if let Some(index) = self.palette_cache.get(&colour) {
+            return *index;
+        }
+        let distance = |p: &[u8; 4]| -> i32 {
+            (0..3).map(|c| (p[c] as i32 - colour[c] as i32).pow(2)).sum()
+        };
+        let (index, _) = self.palette.iter().enumerate().min_by_key(|(_, p)| distance(p)).unwrap();
+        if self.palette_cache.len() >= MAX_CACHED_COLOURS {
+            self.palette_cache.clear();
+        }
+        self.palette_cache.insert(colour, index as u8);
+        index as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK_WHITE_GREY: [[u8; 4]; 3] = [[0, 0, 0, 255], [255, 255, 255, 255], [128, 128, 128, 255]];
+
+    fn recorder(width: usize, height: usize, config: GifConfig) -> GifRecorder<Vec<u8>> {
+        GifRecorder::new(vec![], width, height, BLACK_WHITE_GREY.to_vec(), config).unwrap()
+    }
+
+    #[test]
+    fn frames_are_taken_every_interval() {
+        let recorder = recorder(4, 4, GifConfig { frame_interval: 3, ..GifConfig::default() });
+        let wanted: Vec<u64> = (0..10).filter(|&tick| recorder.wants_frame(tick)).collect();
+        assert_eq!(wanted, vec![0, 3, 6, 9]);
+    }
+
+    #[test]
+    fn downscaling_averages_blocks_into_the_nearest_palette_colour() {
+        let mut image = ImageBuffer::new(4, 2);
+        // Left block all white, right block half white half black
+        image.fill_rect(0, 0, 3, 2, [255, 255, 255, 255]);
+        let mut recorder = recorder(4, 2, GifConfig { downscale: 2, ..GifConfig::default() });
+        recorder.write_frame(&image).unwrap();
+        assert_eq!(recorder.indices, vec![1, 2]);
+
+        let gif = recorder.finish().unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(gif.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (2, 1));
+        assert_eq!(decoder.read_next_frame().unwrap().unwrap().buffer.to_vec(), vec![1, 2]);
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn downscaling_past_the_image_size_averages_what_there_is() {
+        let mut image = ImageBuffer::new(3, 2);
+        image.clear([255, 255, 255, 255]);
+        let mut recorder = recorder(3, 2, GifConfig { downscale: 8, ..GifConfig::default() });
+        recorder.write_frame(&image).unwrap();
+        assert_eq!((recorder.out_width, recorder.out_height, recorder.indices.clone()), (1, 1, vec![1]));
+    }
+
+    #[test]
+    fn the_palette_cache_stays_bounded() {
+        let mut image = ImageBuffer::new(128, 64);
+        for i in 0..128 * 64 {
+            image.set_pixel(i % 128, i / 128, [(i % 256) as u8, (i / 256) as u8, 7, 255]);
+        }
+        let mut recorder = recorder(128, 64, GifConfig::default());
+        recorder.write_frame(&image).unwrap();
+        assert!(recorder.palette_cache.len() <= MAX_CACHED_COLOURS);
+        assert_eq!(recorder.frames_written, 1);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::render::Canvas;
+
+/// Weight at which a heatmap pixel is about two thirds of the way up `heat_colour`
+const HEAT_SCALE: f32 = 3.0;
+/// Colours `heat_colour` passes through, evenly spaced
+const HEAT_RAMP: [[f32; 3]; 5] = [[0.0, 0.0, 0.0], [128.0, 0.0, 40.0], [230.0, 40.0, 0.0], [255.0, 200.0, 0.0], [255.0, 255.0, 255.0]];
+
+/// How particles are drawn over the frame beneath them
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ParticleRendering {
+    /// Each particle is blended onto its pixel by its alpha, later particles covering earlier ones
+    #[default]
+    Overwrite,
+    /// Particles are blended on by their alpha, sharing pixels by averaging their colours
+    Blend,
+    /// Pixels are coloured by how much alpha landed on them, on `heat_colour`
+    Heatmap,
+}
+
+impl ParticleRendering {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "overwrite" => Some(Self::Overwrite),
+            "blend" => Some(Self::Blend),
+            "heatmap" => Some(Self::Heatmap),
+            _ => None,
+        }
+    }
+}
+
+/// Colour of heat `t` from 0 to 1, from black through red and yellow to white
+pub fn heat_colour(t: f32) -> [u8; 4] {
+    let position = t.clamp(0.0, 1.0) * (HEAT_RAMP.len() - 1) as f32;
+    let i = (position as usize).min(HEAT_RAMP.len() - 2);
+    let along = position - i as f32;
+    let channel = |c: usize| (HEAT_RAMP[i][c] + (HEAT_RAMP[i + 1][c] - HEAT_RAMP[i][c]) * along).round() as u8;
+    [channel(0), channel(1), channel(2), 255]
+}
+
+/// Alpha-weighted sum of the colours drawn on each pixel. Fading it instead of clearing it
+/// between frames leaves trails behind whatever moved.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct AccumulationBuffer {
+    pub width: usize,
+    pub height: usize,
+    /// Row by row, each colour scaled by the alpha it was added with
+    colour: Vec<[f32; 3]>,
+    weight: Vec<f32>,
+}
+
+impl AccumulationBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, colour: vec![[0.0; 3]; width * height], weight: vec![0.0; width * height] }
+    }
+
+    /// Keep `keep` of everything added so far, 0 clears the buffer
+    pub fn fade(&mut self, keep: f32) {
+        if keep <= 0.0 {
+            self.colour.fill([0.0; 3]);
+            self.weight.fill(0.0);
+            return;
+        }
+        for (colour, weight) in self.colour.iter_mut().zip(self.weight.iter_mut()) {
+            *colour = colour.map(|channel| channel * keep);
+            *weight *= keep;
+        }
+    }
+
+    /// Add `colour` at `alpha`, positions outside the buffer are ignored
+    pub fn add(&mut self, x: usize, y: usize, colour: [u8; 4], alpha: f32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let i = y * self.width + x;
+        for (sum, channel) in self.colour[i].iter_mut().zip(colour) {
+            *sum += channel as f32 * alpha;
+        }
+        self.weight[i] += alpha;
+    }
+
+    /// Blend the buffer onto `canvas`, which should be the same size. Overwriting isn't
+    /// accumulated, so draws nothing.
+    pub fn composite(&self, canvas: &mut dyn Canvas, rendering: ParticleRendering) {
+        for (i, (&colour, &weight)) in self.colour.iter().zip(self.weight.iter()).enumerate() {
+            // Faded to nothing
+            if weight < 1.0 / 255.0 {
+                continue;
+            }
+            let colour = match rendering {
+                ParticleRendering::Overwrite => return,
+                ParticleRendering::Blend => {
+                    let [r, g, b] = colour.map(|channel| (channel / weight).round() as u8);
+                    [r, g, b, 255]
+                },
+                ParticleRendering::Heatmap => heat_colour(1.0 - (-weight / HEAT_SCALE).exp()),
+            };
+            canvas.blend_pixel(i % self.width, i / self.width, colour, weight.min(1.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::golden::{assert_golden, Tolerance};
+    use crate::render::image_buffer::ImageBuffer;
+
+    #[test]
+    fn trails_fade_and_shared_pixels_average() {
+        let mut buffer = AccumulationBuffer::new(4, 2);
+        buffer.add(1, 1, [200, 0, 0, 255], 1.0);
+        buffer.add(1, 1, [0, 100, 0, 255], 1.0);
+        buffer.add(4, 0, [255, 255, 255, 255], 1.0); // Outside, ignored
+        let mut image = ImageBuffer::new(4, 2);
+        buffer.composite(&mut image, ParticleRendering::Blend);
+        assert_eq!(image.get_pixel(1, 1), [100, 50, 0, 255]);
+        assert_eq!(image.rgba.iter().filter(|&&channel| channel != 0).count(), 3);
+
+        buffer.fade(0.25);
+        image.clear([0, 0, 0, 255]);
+        buffer.composite(&mut image, ParticleRendering::Blend);
+        assert_eq!(image.get_pixel(1, 1), [50, 25, 0, 255]);
+        buffer.fade(0.0);
+        assert_eq!(buffer, AccumulationBuffer::new(4, 2));
+    }
+
+    #[test]
+    fn rendering_modes_match_golden() {
+        let mut image = ImageBuffer::new(64, 24);
+        image.clear([0, 40, 0, 255]);
+        let mut buffer = AccumulationBuffer::new(64, 24);
+        // A fading trail, then piles of overlapping particles that get denser to the right
+        for step in 0..16 {
+            buffer.fade(0.8);
+            buffer.add(2 + step, 4, [80, 160, 255, 255], 1.0);
+        }
+        for pile in 0..8 {
+            for _ in 0..=pile * 2 {
+                buffer.add(4 + pile * 7, 12, [255, 80, 80, 255], 0.5);
+                buffer.add(5 + pile * 7, 12, [80, 255, 80, 255], 0.5);
+            }
+        }
+        buffer.composite(&mut image, ParticleRendering::Blend);
+        let mut heat = AccumulationBuffer::new(64, 24);
+        for pile in 0..8 {
+            for _ in 0..=pile * 2 {
+                heat.add(4 + pile * 7, 20, [255, 255, 255, 255], 0.5);
+            }
+        }
+        heat.composite(&mut image, ParticleRendering::Heatmap);
+        assert_golden("particle_rendering", &image, Tolerance::default());
+    }
+
+    #[test]
+    fn heat_runs_from_black_to_white() {
+        assert_eq!(heat_colour(0.0), [0, 0, 0, 255]);
+        assert_eq!(heat_colour(1.0), [255, 255, 255, 255]);
+        assert_eq!(heat_colour(2.0), [255, 255, 255, 255]);
+        assert_eq!(heat_colour(0.5), [230, 40, 0, 255]);
+    }
+}
@@ -0,0 +1,94 @@
+//! Golden-image comparisons for rendering tests.
+//! References are checked in under `tests/golden/`, run the tests with `UPDATE_GOLDEN=1` to rewrite them.
+//! On a mismatch the actual render and a diff image are written to `target/golden-diff/`.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::render::image_buffer::ImageBuffer;
+use crate::render::Canvas;
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const DIFF_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/golden-diff");
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tolerance {
+    /// Largest per-channel difference that still counts as a matching pixel
+    pub channel: u8,
+    /// How many pixels may differ before the comparison fails
+    pub max_mismatched_pixels: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self { channel: 2, max_mismatched_pixels: 0 }
+    }
+}
+
+pub fn assert_golden(name: &str, actual: &ImageBuffer, tolerance: Tolerance) {
+    let reference_path = Path::new(GOLDEN_DIR).join(format!("{name}.png"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_png(&reference_path, actual);
+        return;
+    }
+    assert!(
+        reference_path.exists(),
+        "missing golden image {}, run with UPDATE_GOLDEN=1 to create it",
+        reference_path.display(),
+    );
+    let reference = read_png(&reference_path);
+    assert!(
+        reference.width == actual.width && reference.height == actual.height,
+        "{name}: rendered {}x{} but the golden image is {}x{}",
+        actual.width, actual.height, reference.width, reference.height,
+    );
+
+    // Mismatched pixels are drawn red over a dimmed copy of the render
+    let mut diff = ImageBuffer::new(actual.width, actual.height);
+    let mut mismatched_pixels = 0;
+    for y in 0..actual.height {
+        for x in 0..actual.width {
+            let a = actual.get_pixel(x, y);
+            let r = reference.get_pixel(x, y);
+            if a.iter().zip(r.iter()).any(|(a, r)| a.abs_diff(*r) > tolerance.channel) {
+                mismatched_pixels += 1;
+                diff.set_pixel(x, y, [255, 0, 0, 255]);
+            } else {
+                diff.set_pixel(x, y, [a[0] / 4, a[1] / 4, a[2] / 4, 255]);
+            }
+        }
+    }
+
+    if mismatched_pixels > tolerance.max_mismatched_pixels {
+        let diff_path = Path::new(DIFF_DIR).join(format!("{name}.diff.png"));
+        let actual_path = Path::new(DIFF_DIR).join(format!("{name}.actual.png"));
+        write_png(&diff_path, &diff);
+        write_png(&actual_path, actual);
+        panic!(
+            "{name}: {mismatched_pixels} pixels differ from the golden image (allowed {}), see {}",
+            tolerance.max_mismatched_pixels,
+            diff_path.display(),
+        );
+    }
+}
+
+fn read_png(path: &Path) -> ImageBuffer {
+    let mut decoder = png::Decoder::new(File::open(path).unwrap());
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba, "golden images must be RGBA");
+    data.truncate(info.buffer_size());
+    ImageBuffer { width: info.width as usize, height: info.height as usize, rgba: data }
+}
+
+fn write_png(path: &Path, image: &ImageBuffer) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let file = BufWriter::new(File::create(path).unwrap());
+    let mut encoder = png::Encoder::new(file, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header().unwrap().write_image_data(&image.rgba).unwrap();
+}
@@ -0,0 +1,113 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor, queue,
+    style::{self, Color, Print},
+};
+
+use crate::render::image_buffer::ImageBuffer;
+use crate::render::Canvas;
+
+/// Canvas that is presented to a terminal using truecolor half-block characters,
+/// two pixels per character cell
+pub struct HalfBlockCanvas {
+    buffer: ImageBuffer,
+}
+
+impl HalfBlockCanvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { buffer: ImageBuffer::new(width, height) }
+    }
+
+    /// Draw the canvas scaled to fit `columns` x `rows` cells: the upper half block
+    /// is coloured with the top pixel and its background with the bottom one
+    pub fn present<W: Write>(&self, out: &mut W, columns: u16, rows: u16) -> io::Result<()> {
+        let (columns, rows) = (columns as usize, rows as usize);
+        let (width, height) = (self.buffer.width, self.buffer.height);
+        if columns == 0 || rows == 0 {
+            return Ok(());
+        }
+        // Keep the aspect ratio by using the same scale on both axes
+        let scale = f32::min(columns as f32 / width as f32, (rows * 2) as f32 / height as f32);
+        let out_width = ((width as f32 * scale) as usize).clamp(1, columns);
+        let out_height = ((height as f32 * scale) as usize).clamp(1, rows * 2);
+        let sample = |x: usize, y: usize| -> Color {
+            let [r, g, b, _] = self.buffer.get_pixel(x * width / out_width, y * height / out_height);
+            Color::Rgb { r, g, b }
+        };
+
+        // Only emit colour changes, large flat tiles would otherwise repeat the same escape codes
+        let mut current: Option<(Color, Color)> = None;
+        for row in 0..out_height.div_ceil(2) {
+            queue!(out, cursor::MoveTo(0, row as u16))?;
+            for x in 0..out_width {
+                let top = sample(x, row * 2);
+                let bottom = if row * 2 + 1 < out_height { sample(x, row * 2 + 1) } else { Color::Reset };
+                if current != Some((top, bottom)) {
+                    queue!(out, style::SetForegroundColor(top), style::SetBackgroundColor(bottom))?;
+                    current = Some((top, bottom));
+                }
+                queue!(out, Print('▀'))?;
+            }
+        }
+        queue!(out, style::ResetColor)
+    }
+}
+
+impl Canvas for HalfBlockCanvas {
+    fn width(&self) -> usize {
+        self.buffer.width
+    }
+
+    fn height(&self) -> usize {
+        self.buffer.height
+    }
+
+    fn get_pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        self.buffer.get_pixel(x, y)
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, colour: [u8; 4]) {
+        self.buffer.set_pixel(x, y, colour);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presented(canvas: &HalfBlockCanvas, columns: u16, rows: u16) -> String {
+        let mut out = vec![];
+        canvas.present(&mut out, columns, rows).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn each_cell_packs_two_pixel_rows_with_a_blank_bottom_on_odd_heights() {
+        let (red, green, blue) = (Color::Rgb { r: 255, g: 0, b: 0 }, Color::Rgb { r: 0, g: 255, b: 0 }, Color::Rgb { r: 0, g: 0, b: 255 });
+        let mut canvas = HalfBlockCanvas::new(1, 3);
+        canvas.set_pixel(0, 0, [255, 0, 0, 255]);
+        canvas.set_pixel(0, 1, [0, 255, 0, 255]);
+        canvas.set_pixel(0, 2, [0, 0, 255, 255]);
+
+        let mut expected = vec![];
+        queue!(expected, cursor::MoveTo(0, 0), style::SetForegroundColor(red), style::SetBackgroundColor(green), Print('▀')).unwrap();
+        queue!(expected, cursor::MoveTo(0, 1), style::SetForegroundColor(blue), style::SetBackgroundColor(Color::Reset), Print('▀')).unwrap();
+        queue!(expected, style::ResetColor).unwrap();
+        assert_eq!(presented(&canvas, 1, 2), String::from_utf8(expected).unwrap());
+    }
+
+    #[test]
+    fn repeated_colours_are_only_set_once() {
+        let mut canvas = HalfBlockCanvas::new(4, 2);
+        canvas.clear([10, 20, 30, 255]);
+        let out = presented(&canvas, 4, 1);
+        assert_eq!(out.matches('▀').count(), 4);
+        assert_eq!(out.matches("38;2;10;20;30").count(), 1);
+    }
+
+    #[test]
+    fn nothing_is_drawn_without_room() {
+        assert_eq!(presented(&HalfBlockCanvas::new(4, 4), 0, 3), "");
+    }
+}
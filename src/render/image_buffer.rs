@@ -0,0 +1,41 @@
+use crate::render::Canvas;
+
+/// Owned in-memory RGBA canvas, used for headless exports and tests
+#[derive(Clone, PartialEq, Debug)]
+pub struct ImageBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+impl ImageBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, rgba: vec![0; width * height * 4] }
+    }
+}
+
+impl Canvas for ImageBuffer {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get_pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        if x >= self.width || y >= self.height {
+            return [0, 0, 0, 0];
+        }
+        let i = (y * self.width + x) * 4;
+        [self.rgba[i], self.rgba[i + 1], self.rgba[i + 2], self.rgba[i + 3]]
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, colour: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let i = (y * self.width + x) * 4;
+        self.rgba[i..i + 4].copy_from_slice(&colour);
+    }
+}
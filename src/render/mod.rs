@@ -0,0 +1,143 @@
+pub mod accumulation;
+pub mod font;
+#[cfg(test)]
+pub mod golden;
+pub mod half_block;
+pub mod image_buffer;
+
+use crate::components::pixel::Pixel;
+
+/// An RGBA drawing surface the simulations render into.
+/// Coordinates outside the canvas are ignored, so callers don't need to clamp.
+pub trait Canvas {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn get_pixel(&self, x: usize, y: usize) -> [u8; 4];
+    fn set_pixel(&mut self, x: usize, y: usize, colour: [u8; 4]);
+
+    /// Mix `colour` over the pixel at (`x`, `y`), `alpha` of the way from what's there
+    fn blend_pixel(&mut self, x: usize, y: usize, colour: [u8; 4], alpha: f32) {
+        let below = self.get_pixel(x, y);
+        let mix = |c: usize| (below[c] as f32 + (colour[c] as f32 - below[c] as f32) * alpha.clamp(0.0, 1.0)).round() as u8;
+        self.set_pixel(x, y, [mix(0), mix(1), mix(2), below[3].max(colour[3])]);
+    }
+
+    fn clear(&mut self, colour: [u8; 4]) {
+        self.fill_rect(0, 0, self.width(), self.height(), colour);
+    }
+
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, colour: [u8; 4]) {
+        for py in y..(y + height).min(self.height()) {
+            for px in x..(x + width).min(self.width()) {
+                self.set_pixel(px, py, colour);
+            }
+        }
+    }
+
+    /// Fill the `tile_size` square at tile grid position (`tile_x`, `tile_y`)
+    fn fill_tile(&mut self, tile_x: usize, tile_y: usize, tile_size: usize, colour: [u8; 4]) {
+        self.fill_rect(tile_x * tile_size, tile_y * tile_size, tile_size, tile_size, colour);
+    }
+
+    /// Copy a row-major sprite `sprite_width` pixels wide with its top left corner at (`x`, `y`)
+    fn blit_sprite(&mut self, x: usize, y: usize, sprite_width: usize, sprite: &[Pixel]) {
+        for (i, pixel) in sprite.iter().enumerate() {
+            self.set_pixel(x + i % sprite_width, y + i / sprite_width, pixel.colour_rgba);
+        }
+    }
+
+    /// Draw `text` in the built in 3x5 font, `scale` screen pixels per font pixel
+    fn draw_text(&mut self, x: usize, y: usize, text: &str, colour: [u8; 4], scale: usize) {
+        for (i, c) in text.chars().enumerate() {
+            let glyph_x = x + i * (font::GLYPH_WIDTH + 1) * scale;
+            for (row, bits) in font::glyph(c).iter().enumerate() {
+                for column in 0..font::GLYPH_WIDTH {
+                    if bits & (0b100 >> column) != 0 {
+                        self.fill_rect(glyph_x + column * scale, y + row * scale, scale, scale, colour);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Canvas over a borrowed RGBA frame, such as the one `Pixels::frame_mut` hands out
+pub struct FrameCanvas<'a> {
+    frame: &'a mut [u8],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> FrameCanvas<'a> {
+    pub fn new(frame: &'a mut [u8], width: usize, height: usize) -> Self {
+        assert_eq!(frame.len(), width * height * 4);
+        Self { frame, width, height }
+    }
+}
+
+impl Canvas for FrameCanvas<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get_pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        if x >= self.width || y >= self.height {
+            return [0, 0, 0, 0];
+        }
+        let i = (y * self.width + x) * 4;
+        [self.frame[i], self.frame[i + 1], self.frame[i + 2], self.frame[i + 3]]
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, colour: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let i = (y * self.width + x) * 4;
+        self.frame[i..i + 4].copy_from_slice(&colour);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::golden::{assert_golden, Tolerance};
+    use crate::render::image_buffer::ImageBuffer;
+
+    #[test]
+    fn primitives_match_golden() {
+        let mut image = ImageBuffer::new(64, 32);
+        image.clear([20, 20, 20, 255]);
+        image.fill_tile(1, 0, 16, [0, 0, 255, 255]);
+        image.fill_rect(40, 4, 30, 6, [255, 125, 0, 255]); // Clipped at the right edge
+        let sprite: Vec<Pixel> = (0..16).map(|i| Pixel::new([i * 16, 255 - i * 16, 0, 255])).collect();
+        image.blit_sprite(2, 2, 4, &sprite);
+        image.draw_text(1, 20, "AB12:", [255, 255, 255, 255], 1);
+        image.draw_text(24, 18, "Z", [0, 255, 0, 255], 2);
+        assert_golden("canvas_primitives", &image, Tolerance::default());
+    }
+
+    #[test]
+    fn frame_canvas_writes_rgba_rows() {
+        let mut frame = vec![0u8; 3 * 2 * 4];
+        let mut canvas = FrameCanvas::new(&mut frame, 3, 2);
+        canvas.set_pixel(2, 1, [1, 2, 3, 4]);
+        canvas.set_pixel(3, 0, [9, 9, 9, 9]); // Out of bounds, ignored
+        assert_eq!(canvas.get_pixel(2, 1), [1, 2, 3, 4]);
+        assert_eq!(&frame[20..24], &[1, 2, 3, 4]);
+        assert_eq!(frame.iter().filter(|b| **b != 0).count(), 4);
+    }
+
+    #[test]
+    fn blending_mixes_towards_the_colour_by_alpha() {
+        let mut image = ImageBuffer::new(2, 1);
+        image.set_pixel(1, 0, [1, 2, 3, 4]);
+        image.blend_pixel(1, 0, [201, 102, 3, 255], 0.5);
+        image.blend_pixel(0, 0, [200, 0, 0, 255], 2.0);
+        assert_eq!(image.get_pixel(1, 0), [101, 52, 3, 255]);
+        assert_eq!(image.get_pixel(0, 0), [200, 0, 0, 255]);
+    }
+}
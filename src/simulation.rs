@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::components::life_grid::LifeGrid;
+use crate::components::physarum::Physarum;
+use crate::components::phylogeny::Phylogeny;
+use crate::components::rule_preset::RulePreset;
+use crate::components::simulation_grid::SimGrid;
+use crate::render::Canvas;
+use crate::PARTICLE_GROUPS_TO_GENERATE;
+
+/// A model the front ends (window, terminal and headless runner) can drive.
+/// New models only need an entry in `SIMULATIONS` to become selectable.
+pub trait Simulation {
+    /// Name used by `--mode` and save files
+    fn name(&self) -> &'static str;
+    fn update(&mut self);
+    fn draw(&mut self, canvas: &mut dyn Canvas);
+    /// Draw everything on the next `draw`, for simulations that only draw what changed
+    fn request_full_redraw(&mut self) {}
+    fn randomise(&mut self);
+    /// Generate the same state every time for a given seed
+    fn randomise_with_seed(&mut self, seed: u64);
+    /// Seed the current state was generated from
+    fn seed(&self) -> u64;
+    /// Labelled values for the HUD and status lines
+    fn stats(&self) -> Vec<(&'static str, String)>;
+    /// Every colour `draw` can use, for palette based exports
+    fn palette(&self) -> Vec<[u8; 4]>;
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()>;
+    /// Replace the current state with one written by `save`
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()>;
+    /// Assert invariants after every update, only honoured in debug builds
+    fn set_check_invariants(&mut self, _check_invariants: bool) {}
+    /// Change a model specific setting, from `--set name=value`
+    fn set_option(&mut self, name: &str, _value: &str) -> Result<(), String> {
+        Err(format!("{} has no option {name}", self.name()))
+    }
+    /// Colour-pair rules as a preset, for models that have them
+    fn rule_preset(&self) -> Option<RulePreset> {
+        None
+    }
+    /// Ancestry of everything born so far, for models that breed
+    fn phylogeny(&self) -> Option<&Phylogeny> {
+        None
+    }
+    /// Replace the rules with `preset`, regenerating the world if it needs different groups
+    fn apply_preset(&mut self, _preset: &RulePreset) -> Result<(), String> {
+        Err(format!("{} has no rules to replace", self.name()))
+    }
+}
+
+/// Settings the front ends apply to every simulation they create or load
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SimulationSettings {
+    pub check_invariants: bool,
+    /// From `--preset`, applied before `options` so they can adjust it
+    pub preset: Option<RulePreset>,
+    /// `--set name=value` pairs for `Simulation::set_option`, applied in order
+    pub options: Vec<(String, String)>,
+}
+
+impl SimulationSettings {
+    /// Parse the value of a `--set` argument
+    pub fn parse_option(value: &str) -> Result<(String, String), String> {
+        match value.split_once('=') {
+            Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+            _ => Err(format!("expected name=value for --set, got {value}")),
+        }
+    }
+
+    /// Apply every setting `simulation` accepts, returning the errors from any it doesn't
+    pub fn apply(&self, simulation: &mut dyn Simulation) -> Result<(), String> {
+        simulation.set_check_invariants(self.check_invariants);
+        let preset_error = self.preset.as_ref().and_then(|preset| simulation.apply_preset(preset).err().map(|err| format!("--preset {}: {err}", preset.name)));
+        let option_errors = self
+            .options
+            .iter()
+            .filter_map(|(name, value)| simulation.set_option(name, value).err().map(|err| format!("--set {name}={value}: {err}")));
+        let errors: Vec<String> = preset_error.into_iter().chain(option_errors).collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Apply the settings a loaded simulation takes. The preset and options are left out, they'd
+    /// replace what the save recorded.
+    pub fn apply_to_loaded(&self, simulation: &mut dyn Simulation) {
+        simulation.set_check_invariants(self.check_invariants);
+    }
+}
+
+pub struct SimulationKind {
+    pub name: &'static str,
+    pub create: fn(width: usize, height: usize) -> Box<dyn Simulation>,
+}
+
+/// Every selectable model, the first one is the default
+pub const SIMULATIONS: &[SimulationKind] = &[
+    SimulationKind { name: "tiles", create: |width, height| Box::new(SimGrid::new(width, height, 1)) },
+    SimulationKind { name: "particles", create: |width, height| Box::new(LifeGrid::new(width, height, PARTICLE_GROUPS_TO_GENERATE)) },
+    SimulationKind { name: "physarum", create: |width, height| Box::new(Physarum::new(width, height)) },
+];
+
+pub fn simulation_names() -> Vec<&'static str> {
+    SIMULATIONS.iter().map(|kind| kind.name).collect()
+}
+
+/// Create the named simulation with a randomised starting state
+pub fn create(name: &str, width: usize, height: usize) -> Option<Box<dyn Simulation>> {
+    let kind = SIMULATIONS.iter().find(|kind| kind.name == name)?;
+    let mut simulation = (kind.create)(width, height);
+    simulation.randomise();
+    Some(simulation)
+}
+
+/// Name of the simulation after `name`, wrapping around
+pub fn next_name(name: &str) -> &'static str {
+    let i = SIMULATIONS.iter().position(|kind| kind.name == name).unwrap_or(0);
+    SIMULATIONS[(i + 1) % SIMULATIONS.len()].name
+}
+
+/// One line summary for status bars
+pub fn status_line(simulation: &dyn Simulation) -> String {
+    let mut status = String::from(simulation.name());
+    for (label, value) in simulation.stats() {
+        status.push_str(&format!(", {label} {value}"));
+    }
+    status
+}
+
+/// Save files start with the simulation's name on its own line, followed by whatever it saves
+pub fn save_file(simulation: &dyn Simulation, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", simulation.name())?;
+    simulation.save(&mut writer)?;
+    writer.flush()
+}
+
+pub fn load_file(path: &Path, width: usize, height: usize) -> io::Result<Box<dyn Simulation>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut name = String::new();
+    reader.read_line(&mut name)?;
+    let kind = SIMULATIONS.iter().find(|kind| kind.name == name.trim()).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("unknown simulation {:?} in save file", name.trim()))
+    })?;
+    let mut simulation = (kind.create)(width, height);
+    simulation.load(&mut reader)?;
+    Ok(simulation)
+}
+
+/// Random generator the models draw from. Its state goes in their saves, so a loaded run carries on
+/// exactly as the saved one would have.
+pub type SimRng = ChaCha12Rng;
+
+/// Generator for a loaded model to carry on with. Saves from before generators were saved don't
+/// have one, for those it's derived from the `seed` and how far the run had got.
+pub fn loaded_rng(saved: Option<SimRng>, seed: u64, progress: u64) -> SimRng {
+    saved.unwrap_or_else(|| SimRng::seed_from_u64(seed.wrapping_add(progress)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_simulation_round_trips_through_its_save() {
+        for kind in SIMULATIONS {
+            let mut simulation = (kind.create)(64, 48);
+            assert_eq!(simulation.name(), kind.name);
+            simulation.randomise_with_seed(11);
+            simulation.update();
+
+            let mut saved = vec![];
+            simulation.save(&mut saved).unwrap();
+            let mut loaded = (kind.create)(1, 1);
+            loaded.load(&mut saved.as_slice()).unwrap();
+            assert_eq!(loaded.seed(), 11, "{}", kind.name);
+            assert_eq!(status_line(loaded.as_ref()), status_line(simulation.as_ref()), "{}", kind.name);
+        }
+    }
+
+    #[test]
+    fn loaded_runs_carry_on_as_if_never_saved() {
+        let save = |simulation: &dyn Simulation| {
+            let mut saved = vec![];
+            simulation.save(&mut saved).unwrap();
+            saved
+        };
+        for kind in SIMULATIONS {
+            let mut simulation = (kind.create)(96, 64);
+            simulation.randomise_with_seed(7);
+            simulation.update();
+            let mut loaded = (kind.create)(1, 1);
+            loaded.load(&mut save(simulation.as_ref()).as_slice()).unwrap();
+            for _ in 0..3 {
+                simulation.update();
+                loaded.update();
+            }
+            assert!(save(loaded.as_ref()) == save(simulation.as_ref()), "{} differs after loading", kind.name);
+        }
+    }
+
+    #[test]
+    fn rejected_particle_saves_leave_the_world_as_it_was() {
+        let mut world = LifeGrid::new(64, 48, 3);
+        world.randomise_with_seed(8);
+        let mut saved = vec![];
+        world.save(&mut saved).unwrap();
+        let mut save: serde_json::Value = serde_json::from_slice(&saved).unwrap();
+        save["bonds"] = serde_json::json!([{ "a": u32::MAX, "b": u32::MAX - 1, "strength": 1.0, "length": 5.0, "max_length": 10.0 }]);
+        assert!(world.load(&mut save.to_string().as_bytes()).is_err());
+        let mut after = vec![];
+        world.save(&mut after).unwrap();
+        assert!(after == saved);
+    }
+
+    #[test]
+    fn thread_count_does_not_change_results() {
+        let run = |threads: usize, kind: &SimulationKind, settings: &SimulationSettings| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                let mut simulation = (kind.create)(96, 64);
+                settings.apply(simulation.as_mut()).unwrap();
+                simulation.randomise_with_seed(5);
+                for _ in 0..3 {
+                    simulation.update();
+                }
+                let mut saved = vec![];
+                simulation.save(&mut saved).unwrap();
+                saved
+            })
+        };
+        let with = |name: &str, value: &str| SimulationSettings { options: vec![(String::from(name), String::from(value))], ..Default::default() };
+        let particle_settings = vec![SimulationSettings::default(), with("barnes_hut_theta", "0.5"), with("boundary", "wrap")];
+        for kind in SIMULATIONS {
+            let settings = if kind.name == "particles" { particle_settings.clone() } else { vec![SimulationSettings::default()] };
+            for settings in settings {
+                assert!(run(1, kind, &settings) == run(4, kind, &settings), "{} with {:?} differs between 1 and 4 threads", kind.name, settings.options);
+            }
+        }
+    }
+
+    #[test]
+    fn next_name_cycles_through_every_simulation() {
+        let mut name = SIMULATIONS[0].name;
+        for kind in SIMULATIONS.iter().skip(1) {
+            name = next_name(name);
+            assert_eq!(name, kind.name);
+        }
+        assert_eq!(next_name(name), SIMULATIONS[0].name);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::components::force_kernel::ForceKernel;
+use crate::spatial::quadtree::{Aabb, Point};
+
+/// Nodes with this many bodies or fewer are summed exactly
+const LEAF_SIZE: usize = 8;
+/// Stops endless splitting when many bodies share a position
+const MAX_DEPTH: u32 = 16;
+
+#[derive(Clone, Copy, Debug)]
+struct Body {
+    x: f32,
+    y: f32,
+    group: usize,
+}
+
+/// Mass and position sum of one group's bodies under a node
+#[derive(Clone, Copy, Debug, Default)]
+struct GroupMass {
+    mass: f32,
+    sum_x: f32,
+    sum_y: f32,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    boundary: Aabb,
+    /// The four children are stored together from here, in `Aabb::quarters` order. 0 for leaves.
+    first_child: usize,
+    /// Range of `bodies` under this node
+    start: usize,
+    end: usize,
+}
+
+/// Quadtree over every particle that keeps the mass and centroid of each colour group per node,
+/// so far away clusters of a group can be treated as a single body.
+/// Rebuilt from scratch every tick.
+#[derive(Clone, Debug, Default)]
+pub struct BarnesHutTree {
+    group_count: usize,
+    nodes: Vec<Node>,
+    /// `group_count` entries per node
+    masses: Vec<GroupMass>,
+    /// Reordered so every node's bodies are contiguous
+    bodies: Vec<Body>,
+}
+
+impl BarnesHutTree {
+    /// Build from each group's particle positions
+    pub fn build<G: IntoIterator<Item = (f32, f32)>>(groups: impl IntoIterator<Item = G>) -> Self {
+        let mut tree = Self::default();
+        for (group, positions) in groups.into_iter().enumerate() {
+            tree.group_count = group + 1;
+            tree.bodies.extend(positions.into_iter().map(|(x, y)| Body { x, y, group }));
+        }
+        tree.build_nodes();
+        tree
+    }
+
+    fn build_nodes(&mut self) {
+        // Fit the root around every body, including any briefly outside the world
+        let mut min = Point::new(f32::MAX, f32::MAX);
+        let mut max = Point::new(f32::MIN, f32::MIN);
+        for body in self.bodies.iter() {
+            min = Point::new(min.x.min(body.x), min.y.min(body.y));
+            max = Point::new(max.x.max(body.x), max.y.max(body.y));
+        }
+        if self.bodies.is_empty() {
+            (min, max) = (Point::default(), Point::default());
+        }
+        self.push_node(Aabb { min, max }, 0, self.bodies.len());
+        self.split(0, 0);
+    }
+
+    fn push_node(&mut self, boundary: Aabb, start: usize, end: usize) {
+        self.nodes.push(Node { boundary, first_child: 0, start, end });
+        self.masses.extend(std::iter::repeat_n(GroupMass::default(), self.group_count));
+    }
+
+    fn masses(&self, node: usize) -> &[GroupMass] {
+        &self.masses[node * self.group_count..(node + 1) * self.group_count]
+    }
+
+    fn split(&mut self, node: usize, depth: u32) {
+        let Node { boundary, start, end, .. } = self.nodes[node];
+        if end - start > LEAF_SIZE && depth < MAX_DEPTH {
+            self.bodies[start..end].sort_unstable_by_key(|body| boundary.quadrant(Point::new(body.x, body.y)));
+            let first_child = self.nodes.len();
+            self.nodes[node].first_child = first_child;
+            let mut child_start = start;
+            for (quadrant, quarter) in boundary.quarters().into_iter().enumerate() {
+                let child_end = child_start + self.bodies[child_start..end].partition_point(|body| boundary.quadrant(Point::new(body.x, body.y)) == quadrant);
+                self.push_node(quarter, child_start, child_end);
+                child_start = child_end;
+            }
+            for child in first_child..first_child + 4 {
+                self.split(child, depth + 1);
+                for group in 0..self.group_count {
+                    let child_mass = self.masses[child * self.group_count + group];
+                    let mass = &mut self.masses[node * self.group_count + group];
+                    mass.mass += child_mass.mass;
+                    mass.sum_x += child_mass.sum_x;
+                    mass.sum_y += child_mass.sum_y;
+                }
+            }
+        } else {
+            for i in start..end {
+                let body = self.bodies[i];
+                let mass = &mut self.masses[node * self.group_count + body.group];
+                mass.mass += 1.0;
+                mass.sum_x += body.x;
+                mass.sum_y += body.y;
+            }
+        }
+    }
+
+    /// Approximate the rule force `group` exerts on a particle at (`x`, `y`), matching
+    /// `ParticleStore::rule_forces`: each body within `radius` pushes as `kernel` says.
+    /// A node is treated as one body at its group's centroid when it sits entirely within `radius`
+    /// and its size over its distance is below `theta`, so `theta` = 0 gives the exact sum.
+    #[allow(clippy::too_many_arguments)]
+    pub fn rule_force<K: ForceKernel>(&self, x: f32, y: f32, group: usize, kernel: &K, g: f32, radius: f32, theta: f32) -> (f32, f32) {
+        let mut force = (0.0, 0.0);
+        if group < self.group_count && !self.nodes.is_empty() {
+            self.accumulate(0, Point::new(x, y), group, kernel, g, radius, theta, &mut force);
+        }
+        force
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn accumulate<K: ForceKernel>(&self, node: usize, point: Point, group: usize, kernel: &K, g: f32, radius: f32, theta: f32, force: &mut (f32, f32)) {
+        let Node { boundary, first_child, start, end } = self.nodes[node];
+        let mass = self.masses(node)[group];
+        if mass.mass == 0.0 || boundary.distance_squared(point) >= radius * radius {
+            return;
+        }
+
+        if first_child != 0 {
+            let dx = (point.x - boundary.min.x).abs().max((point.x - boundary.max.x).abs());
+            let dy = (point.y - boundary.min.y).abs().max((point.y - boundary.max.y).abs());
+            let inside_radius = dx * dx + dy * dy < radius * radius;
+            let size = (boundary.max.x - boundary.min.x).max(boundary.max.y - boundary.min.y);
+            let centroid = Point::new(mass.sum_x / mass.mass, mass.sum_y / mass.mass);
+            let d = point.distance_squared(centroid).sqrt();
+            if inside_radius && size < theta * d {
+                let force_per_distance = kernel.force(d, radius, g) * mass.mass / d;
+                force.0 += force_per_distance * (point.x - centroid.x);
+                force.1 += force_per_distance * (point.y - centroid.y);
+            } else {
+                for child in first_child..first_child + 4 {
+                    self.accumulate(child, point, group, kernel, g, radius, theta, force);
+                }
+            }
+            return;
+        }
+
+        for body in self.bodies[start..end].iter().filter(|body| body.group == group) {
+            let dx = point.x - body.x;
+            let dy = point.y - body.y;
+            let d = (dx * dx + dy * dy).sqrt();
+            if d > 0.0 && d < radius {
+                let force_per_distance = kernel.force(d, radius, g) / d;
+                force.0 += force_per_distance * dx;
+                force.1 += force_per_distance * dy;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench;
+    use crate::components::force_kernel::Kernel;
+    use proptest::prelude::*;
+
+    /// The sum `ParticleStore::rule_forces` computes with the constant kernel, and how many bodies contributed
+    fn exact(points: &[(f32, f32)], x: f32, y: f32, g: f32, radius: f32) -> ((f32, f32), usize) {
+        let mut force = (0.0, 0.0);
+        let mut count = 0;
+        for &(px, py) in points {
+            let (dx, dy) = (x - px, y - py);
+            let d = (dx * dx + dy * dy).sqrt();
+            if d > 0.0 && d < radius {
+                force.0 += g / d * dx;
+                force.1 += g / d * dy;
+                count += 1;
+            }
+        }
+        (force, count)
+    }
+
+    proptest! {
+        #[test]
+        fn error_is_bounded_by_theta(
+            groups in prop::collection::vec(prop::collection::vec((0.0f32..500.0, 0.0f32..500.0), 0..400), 1..4),
+            (x, y) in (0.0f32..500.0, 0.0f32..500.0),
+            g in -1.0f32..1.0,
+            radius in 1.0f32..600.0,
+            theta in 0.0f32..1.0,
+        ) {
+            let tree = BarnesHutTree::build(groups.iter().map(|group| group.iter().copied()));
+            for (i, group) in groups.iter().enumerate() {
+                let (fx, fy) = tree.rule_force(x, y, i, &Kernel::Constant, g, radius, theta);
+                let ((ex, ey), count) = exact(group, x, y, g, radius);
+                // Every body pushes with strength |g|, and treating a cluster as one body turns each
+                // body's push by at most about sqrt(2) * theta radians
+                let error = ((fx - ex).powi(2) + (fy - ey).powi(2)).sqrt();
+                prop_assert!(error <= (1.5 * theta * g.abs() + 1e-3) * count.max(1) as f32, "error {error} for {count} bodies");
+            }
+        }
+    }
+
+    #[test]
+    fn theta_zero_is_exact() {
+        let points = bench::random_points(2_000, 300, 300);
+        let tree = BarnesHutTree::build([points.iter().copied()]);
+        for &(x, y) in points.iter().take(50) {
+            let (fx, fy) = tree.rule_force(x, y, 0, &Kernel::Constant, 0.5, 100.0, 0.0);
+            let ((ex, ey), _) = exact(&points, x, y, 0.5, 100.0);
+            assert!((fx - ex).abs() < 1e-2 && (fy - ey).abs() < 1e-2);
+        }
+    }
+
+    /// `cargo test --release barnes_hut_scaling -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn barnes_hut_scaling() {
+        const RADIUS: f32 = 250.0;
+        const THETA: f32 = 0.5;
+        for count in [10_000, 100_000] {
+            let points = bench::random_points(count, 1000, 1000);
+            let samples = &points[..1000];
+            let exact_forces = bench::time(&format!("exact, 1000 of {count} particles"), 1, || {
+                samples.iter().map(|&(x, y)| exact(&points, x, y, 1.0, RADIUS).0).collect::<Vec<_>>()
+            });
+            let tree = bench::time(&format!("barnes-hut build, {count} particles"), 5, || BarnesHutTree::build([points.iter().copied()]));
+            let forces = bench::time(&format!("barnes-hut, 1000 of {count} particles"), 5, || {
+                samples.iter().map(|&(x, y)| tree.rule_force(x, y, 0, &Kernel::Constant, 1.0, RADIUS, THETA)).collect::<Vec<_>>()
+            });
+            let mean_error = forces.iter().zip(exact_forces.iter()).map(|(f, e)| ((f.0 - e.0).powi(2) + (f.1 - e.1).powi(2)).sqrt()).sum::<f32>() / samples.len() as f32;
+            let mean_force = exact_forces.iter().map(|e| (e.0 * e.0 + e.1 * e.1).sqrt()).sum::<f32>() / samples.len() as f32;
+            println!("  mean error {mean_error:.2} against a mean force of {mean_force:.2}");
+        }
+    }
+}
@@ -0,0 +1,75 @@
+pub mod barnes_hut;
+pub mod quadtree;
+pub mod spatial_hash;
+
+/// A 2D index of items by position, answering "what is near this point" without checking every item.
+pub trait SpatialIndex<T: Copy> {
+    /// Remove every item, ready for the next rebuild
+    fn clear(&mut self);
+    fn insert(&mut self, x: f32, y: f32, item: T);
+    /// Push every item within `radius` of (`x`, `y`) onto `found`, in a deterministic order
+    fn query_radius(&self, x: f32, y: f32, radius: f32, found: &mut Vec<T>);
+}
+
+/// Rebuild `index` from scratch with each point's index in `points` as its item
+#[cfg(test)]
+pub fn rebuild<I: SpatialIndex<usize>>(index: &mut I, points: impl Iterator<Item = (f32, f32)>) {
+    index.clear();
+    for (i, (x, y)) in points.enumerate() {
+        index.insert(x, y, i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::quadtree::{Aabb, Quadtree};
+    use super::spatial_hash::SpatialHash;
+    use super::*;
+    use crate::bench;
+
+    const RADIUS: f32 = 100.0;
+
+    /// Rebuild `index` and query around every point, returning the mean neighbour count
+    fn bench_index<I: SpatialIndex<usize>>(label: &str, index: &mut I, points: &[(f32, f32)]) -> f32 {
+        let mut found = vec![];
+        let neighbours = bench::time(label, 5, || {
+            rebuild(index, points.iter().copied());
+            let mut neighbours = 0;
+            for &(x, y) in points.iter() {
+                found.clear();
+                index.query_radius(x, y, RADIUS, &mut found);
+                neighbours += found.len();
+            }
+            neighbours
+        });
+        neighbours as f32 / points.len() as f32
+    }
+
+    /// `cargo test --release spatial_index_scaling -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn spatial_index_scaling() {
+        for count in [1_000, 10_000, 100_000] {
+            // Grow the world with the particle count so each particle keeps ~30 neighbours
+            let side = (count as f32 * std::f32::consts::PI * RADIUS * RADIUS / 30.0).sqrt() as usize;
+            let points = bench::random_points(count, side, side);
+
+            let mut hash = SpatialHash::new(side, side, RADIUS);
+            let neighbours = bench_index(&format!("spatial hash, {count} particles"), &mut hash, &points);
+            let mut tree = Quadtree::new(Aabb::from_rect(0.0, 0.0, side as f32, side as f32));
+            assert_eq!(bench_index(&format!("quadtree, {count} particles"), &mut tree, &points), neighbours);
+            println!("  {neighbours:.1} neighbours per particle");
+
+            // Brute force is quadratic, 100k particles would take minutes
+            if count <= 10_000 {
+                bench::time(&format!("brute force, {count} particles"), 1, || {
+                    let mut neighbours = 0;
+                    for &(x, y) in points.iter() {
+                        neighbours += points.iter().filter(|&&(px, py)| (px - x) * (px - x) + (py - y) * (py - y) <= RADIUS * RADIUS).count();
+                    }
+                    neighbours
+                });
+            }
+        }
+    }
+}
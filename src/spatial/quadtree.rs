@@ -0,0 +1,294 @@
+use crate::spatial::SpatialIndex;
+
+/// Leaves split once they hold more items than this
+const NODE_CAPACITY: usize = 8;
+/// Stops endless splitting when many items share a position, deeper leaves just grow
+const MAX_DEPTH: u32 = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn distance_squared(&self, other: Point) -> f32 {
+        let dx = self.x - other.x;
+        let dy = self.y - other.y;
+        dx * dx + dy * dy
+    }
+}
+
+/// Axis aligned box, edges included
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Aabb {
+    /// Box with its top left corner at (`x`, `y`)
+    pub fn from_rect(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { min: Point::new(x, y), max: Point::new(x + width, y + height) }
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new((self.min.x + self.max.x) / 2.0, (self.min.y + self.max.y) / 2.0)
+    }
+
+    pub fn contains_point(&self, point: Point) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x && point.y >= self.min.y && point.y <= self.max.y
+    }
+
+    /// Squared distance from `point` to the nearest point in the box, 0 inside it
+    pub fn distance_squared(&self, point: Point) -> f32 {
+        let dx = (self.min.x - point.x).max(point.x - self.max.x).max(0.0);
+        let dy = (self.min.y - point.y).max(point.y - self.max.y).max(0.0);
+        dx * dx + dy * dy
+    }
+
+    /// Index into `quarters` of the quarter `point` falls in, points on a split go east and south
+    pub fn quadrant(&self, point: Point) -> usize {
+        let center = self.center();
+        let east = point.x >= center.x;
+        let south = point.y >= center.y;
+        east as usize + 2 * south as usize
+    }
+
+    /// North west, north east, south west and south east quarters.
+    /// They share their edges exactly with each other and this box.
+    pub fn quarters(&self) -> [Aabb; 4] {
+        let (min, center, max) = (self.min, self.center(), self.max);
+        [
+            Aabb { min, max: center },
+            Aabb { min: Point::new(center.x, min.y), max: Point::new(max.x, center.y) },
+            Aabb { min: Point::new(min.x, center.y), max: Point::new(center.x, max.y) },
+            Aabb { min: center, max },
+        ]
+    }
+}
+
+/// Point quadtree holding copyable items, e.g. particle indices or tile occupants.
+/// Items outside the boundary are kept in a flat list that every query also checks,
+/// which keeps particles that have briefly left the world findable.
+#[derive(Clone, Debug)]
+pub struct Quadtree<T> {
+    root: Node<T>,
+    outside: Vec<(Point, T)>,
+}
+
+#[derive(Clone, Debug)]
+struct Node<T> {
+    boundary: Aabb,
+    depth: u32,
+    /// Only leaves hold items
+    items: Vec<(Point, T)>,
+    /// In `Aabb::quarters` order
+    children: Option<Box<[Node<T>; 4]>>,
+}
+
+impl<T: Copy> Quadtree<T> {
+    pub fn new(boundary: Aabb) -> Self {
+        Self { root: Node::new(boundary, 0), outside: vec![] }
+    }
+
+    pub fn insert_at(&mut self, point: Point, item: T) {
+        if self.root.boundary.contains_point(point) {
+            self.root.insert(point, item);
+        } else {
+            self.outside.push((point, item));
+        }
+    }
+
+    /// Up to `k` items closest to `point`, nearest first. Ties keep the order the tree found them in.
+    pub fn nearest(&self, point: Point, k: usize) -> Vec<T> {
+        let mut best: Vec<(f32, T)> = Vec::with_capacity(k + 1);
+        if k == 0 {
+            return vec![];
+        }
+        for &(other, item) in self.outside.iter() {
+            push_nearest(&mut best, k, point.distance_squared(other), item);
+        }
+        self.root.nearest(point, k, &mut best);
+        best.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+/// Keep `best` sorted by distance and no longer than `k`
+fn push_nearest<T>(best: &mut Vec<(f32, T)>, k: usize, distance_squared: f32, item: T) {
+    if best.len() == k && distance_squared >= best[k - 1].0 {
+        return;
+    }
+    let i = best.partition_point(|(other, _)| *other <= distance_squared);
+    best.insert(i, (distance_squared, item));
+    best.truncate(k);
+}
+
+impl<T: Copy> Node<T> {
+    fn new(boundary: Aabb, depth: u32) -> Self {
+        Self { boundary, depth, items: vec![], children: None }
+    }
+
+    fn insert(&mut self, point: Point, item: T) {
+        if let Some(children) = self.children.as_mut() {
+            children[self.boundary.quadrant(point)].insert(point, item);
+            return;
+        }
+        self.items.push((point, item));
+        if self.items.len() > NODE_CAPACITY && self.depth < MAX_DEPTH {
+            self.subdivide();
+        }
+    }
+
+    fn subdivide(&mut self) {
+        let [nw, ne, sw, se] = self.boundary.quarters();
+        let depth = self.depth + 1;
+        let mut children = Box::new([Node::new(nw, depth), Node::new(ne, depth), Node::new(sw, depth), Node::new(se, depth)]);
+        for (point, item) in self.items.drain(..) {
+            children[self.boundary.quadrant(point)].insert(point, item);
+        }
+        self.children = Some(children);
+    }
+
+    fn query_radius(&self, point: Point, radius_squared: f32, found: &mut Vec<T>) {
+        if self.boundary.distance_squared(point) > radius_squared {
+            return;
+        }
+        match self.children.as_ref() {
+            Some(children) => {
+                for child in children.iter() {
+                    child.query_radius(point, radius_squared, found);
+                }
+            },
+            None => {
+                found.extend(self.items.iter().filter(|(other, _)| point.distance_squared(*other) <= radius_squared).map(|&(_, item)| item));
+            },
+        }
+    }
+
+    fn nearest(&self, point: Point, k: usize, best: &mut Vec<(f32, T)>) {
+        if best.len() == k && self.boundary.distance_squared(point) >= best[k - 1].0 {
+            return;
+        }
+        match self.children.as_ref() {
+            Some(children) => {
+                // Closest children first, so the rest are more likely to be skipped
+                let mut order = [0, 1, 2, 3];
+                order.sort_by(|&a, &b| children[a].boundary.distance_squared(point).total_cmp(&children[b].boundary.distance_squared(point)));
+                for i in order {
+                    children[i].nearest(point, k, best);
+                }
+            },
+            None => {
+                for &(other, item) in self.items.iter() {
+                    push_nearest(best, k, point.distance_squared(other), item);
+                }
+            },
+        }
+    }
+}
+
+impl<T: Copy> SpatialIndex<T> for Quadtree<T> {
+    fn clear(&mut self) {
+        *self = Self::new(self.root.boundary);
+    }
+
+    fn insert(&mut self, x: f32, y: f32, item: T) {
+        self.insert_at(Point::new(x, y), item);
+    }
+
+    fn query_radius(&self, x: f32, y: f32, radius: f32, found: &mut Vec<T>) {
+        let point = Point::new(x, y);
+        let radius_squared = radius * radius;
+        self.root.query_radius(point, radius_squared, found);
+        found.extend(self.outside.iter().filter(|(other, _)| point.distance_squared(*other) <= radius_squared).map(|&(_, item)| item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn world() -> Aabb {
+        Aabb::from_rect(0.0, 0.0, 250.0, 250.0)
+    }
+
+    fn build(points: &[(f32, f32)]) -> Quadtree<usize> {
+        let mut tree = Quadtree::new(world());
+        crate::spatial::rebuild(&mut tree, points.iter().copied());
+        tree
+    }
+
+    fn sorted(mut items: Vec<usize>) -> Vec<usize> {
+        items.sort_unstable();
+        items
+    }
+
+    fn point_strategy() -> impl Strategy<Value = (f32, f32)> {
+        // Some points just outside the world, and a few repeats of one position to force deep splits
+        prop_oneof![
+            4 => (-20.0f32..270.0, -20.0f32..270.0),
+            1 => Just((100.0, 100.0)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn radius_query_matches_brute_force(
+            points in prop::collection::vec(point_strategy(), 0..300),
+            (x, y, radius) in (-20.0f32..270.0, -20.0f32..270.0, 0.0f32..150.0),
+        ) {
+            let tree = build(&points);
+            let mut found = vec![];
+            tree.query_radius(x, y, radius, &mut found);
+            let centre = Point::new(x, y);
+            let expected: Vec<usize> = (0..points.len())
+                .filter(|&i| centre.distance_squared(Point::new(points[i].0, points[i].1)) <= radius * radius)
+                .collect();
+            prop_assert_eq!(sorted(found), expected);
+        }
+
+        #[test]
+        fn nearest_matches_brute_force(
+            points in prop::collection::vec(point_strategy(), 0..300),
+            (x, y) in (-20.0f32..270.0, -20.0f32..270.0),
+            k in 0usize..20,
+        ) {
+            let tree = build(&points);
+            let centre = Point::new(x, y);
+            let distance = |i: usize| centre.distance_squared(Point::new(points[i].0, points[i].1));
+            let found: Vec<f32> = tree.nearest(centre, k).into_iter().map(distance).collect();
+            // Compare distances rather than items, as equally distant items can come back in any order
+            let mut expected: Vec<f32> = (0..points.len()).map(distance).collect();
+            expected.sort_by(f32::total_cmp);
+            expected.truncate(k);
+            prop_assert_eq!(found, expected);
+        }
+
+    }
+
+    #[test]
+    fn holds_tile_occupants() {
+        // Anything Copy works as an item, not just indices
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        enum Occupant {
+            Particle(u32),
+            Resource(char),
+        }
+        let mut tree = Quadtree::new(Aabb::from_rect(0.0, 0.0, 64.0, 64.0));
+        tree.insert_at(Point::new(40.0, 8.0), Occupant::Resource('f'));
+        tree.insert_at(Point::new(4.0, 4.0), Occupant::Particle(7));
+        tree.insert_at(Point::new(36.0, 12.0), Occupant::Particle(9));
+
+        // Around the middle of the tile at (1, 0) with 32 pixel tiles
+        let mut found = vec![];
+        tree.query_radius(48.0, 16.0, 16.0, &mut found);
+        assert_eq!(found, vec![Occupant::Resource('f'), Occupant::Particle(9)]);
+        assert_eq!(tree.nearest(Point::new(0.0, 0.0), 1), vec![Occupant::Particle(7)]);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::spatial::SpatialIndex;
+
+/// Uniform grid of square cells over a `width` x `height` world.
+/// Points outside the world are kept in the nearest edge cell, so nothing is lost while a particle
+/// is briefly out of bounds. Cheapest to rebuild every tick; a cell size close to the usual query
+/// radius keeps each query down to a 3x3 block of cells.
+#[derive(Clone, Debug)]
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<(f32, f32, T)>>,
+}
+
+impl<T: Copy> SpatialHash<T> {
+    pub fn new(width: usize, height: usize, cell_size: f32) -> Self {
+        assert!(cell_size > 0.0);
+        let columns = ((width as f32 / cell_size).ceil() as usize).max(1);
+        let rows = ((height as f32 / cell_size).ceil() as usize).max(1);
+        Self { cell_size, columns, rows, cells: vec![vec![]; columns * rows] }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn column(&self, x: f32) -> usize {
+        // `as` saturates, so negative and NaN positions land in the first column
+        ((x / self.cell_size) as usize).min(self.columns - 1)
+    }
+
+    fn row(&self, y: f32) -> usize {
+        ((y / self.cell_size) as usize).min(self.rows - 1)
+    }
+}
+
+impl<T: Copy> SpatialIndex<T> for SpatialHash<T> {
+    fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+    }
+
+    fn insert(&mut self, x: f32, y: f32, item: T) {
+        let i = self.row(y) * self.columns + self.column(x);
+        self.cells[i].push((x, y, item));
+    }
+
+    fn query_radius(&self, x: f32, y: f32, radius: f32, found: &mut Vec<T>) {
+        let radius_squared = radius * radius;
+        for row in self.row(y - radius)..=self.row(y + radius) {
+            for column in self.column(x - radius)..=self.column(x + radius) {
+                for &(px, py, item) in self.cells[row * self.columns + column].iter() {
+                    let dx = px - x;
+                    let dy = py - y;
+                    if dx * dx + dy * dy <= radius_squared {
+                        found.push(item);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spatial::rebuild;
+    use proptest::prelude::*;
+
+    fn brute_force(points: &[(f32, f32)], x: f32, y: f32, radius: f32) -> Vec<usize> {
+        (0..points.len())
+            .filter(|&i| {
+                let (dx, dy) = (points[i].0 - x, points[i].1 - y);
+                dx * dx + dy * dy <= radius * radius
+            })
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn finds_the_same_neighbours_as_brute_force(
+            // Includes points a little outside the world, like particles mid-bounce
+            points in prop::collection::vec((-20.0f32..270.0, -20.0f32..270.0), 0..300),
+            (x, y, radius) in (-20.0f32..270.0, -20.0f32..270.0, 0.0f32..150.0),
+            cell_size in 1.0f32..120.0,
+        ) {
+            let mut hash = SpatialHash::new(250, 250, cell_size);
+            rebuild(&mut hash, points.iter().copied());
+            let mut found = vec![];
+            hash.query_radius(x, y, radius, &mut found);
+            found.sort_unstable();
+            prop_assert_eq!(found, brute_force(&points, x, y, radius));
+        }
+    }
+}
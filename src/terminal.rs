@@ -0,0 +1,143 @@
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    queue,
+    style::{self, Print},
+    terminal::{self, ClearType},
+};
+
+use crate::render::half_block::HalfBlockCanvas;
+use crate::simulation::{self, Simulation, SimulationSettings};
+use crate::{create_simulation, load_simulation, save_simulation, HEIGHT, WIDTH};
+
+/// Time between simulation updates while running
+const TICK_RATE: Duration = Duration::from_millis(50);
+
+/// Puts the terminal into raw mode on an alternate screen and restores it when dropped,
+/// including when the simulation panics
+struct TerminalGuard {
+    stdout: Stdout,
+}
+
+impl TerminalGuard {
+    fn new() -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        queue!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+        stdout.flush()?;
+        Ok(Self { stdout })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = queue!(self.stdout, style::ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = self.stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Run the simulation in the terminal using truecolor half-block characters,
+/// with the same P (pause), Space (step), R (randomise), M (mode), S (save) and L (load) controls as the window
+pub fn run(mut simulation: Box<dyn Simulation>, settings: &SimulationSettings) -> io::Result<()> {
+    let (width, height) = (WIDTH as usize, HEIGHT as usize);
+    let mut canvas = HalfBlockCanvas::new(width, height);
+
+    let mut guard = TerminalGuard::new()?;
+    let mut size = terminal::size()?;
+    let mut paused = false;
+    let mut tick: u64 = 0;
+    let mut last_update = Instant::now();
+    let mut needs_redraw = true;
+
+    loop {
+        let timeout = TICK_RATE.saturating_sub(last_update.elapsed());
+        if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(KeyEvent { code, kind: KeyEventKind::Press, .. }) => match code {
+                    KeyCode::Esc | KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Char('p') => paused = !paused,
+                    KeyCode::Char(' ') => {
+                        // Space is frame-step, so ensure we're paused
+                        paused = true;
+                        simulation.update();
+                        tick += 1;
+                    },
+                    KeyCode::Char('r') => simulation.randomise(),
+                    KeyCode::Char('m') => {
+                        simulation = create_simulation(simulation::next_name(simulation.name()), settings);
+                        tick = 0;
+                    },
+                    KeyCode::Char('s') => save_simulation(simulation.as_ref()),
+                    KeyCode::Char('l') => {
+                        if let Some(loaded) = load_simulation(settings) {
+                            simulation = loaded;
+                            tick = 0;
+                        }
+                    },
+                    _ => {},
+                },
+                Event::Resize(columns, rows) => {
+                    size = (columns, rows);
+                    queue!(guard.stdout, terminal::Clear(ClearType::All))?;
+                },
+                _ => {},
+            }
+            needs_redraw = true;
+        }
+
+        if last_update.elapsed() >= TICK_RATE {
+            last_update = Instant::now();
+            if !paused {
+                simulation.update();
+                tick += 1;
+                needs_redraw = true;
+            }
+        }
+
+        if needs_redraw {
+            needs_redraw = false;
+            simulation.draw(&mut canvas);
+            let (columns, rows) = size;
+            // The last row is reserved for the status line
+            canvas.present(&mut guard.stdout, columns, rows.saturating_sub(1))?;
+            let status = status_text(tick, paused, simulation.as_ref(), columns);
+            queue!(
+                guard.stdout,
+                cursor::MoveTo(0, rows.saturating_sub(1)),
+                style::ResetColor,
+                terminal::Clear(ClearType::CurrentLine),
+                Print(status),
+            )?;
+            guard.stdout.flush()?;
+        }
+    }
+}
+
+/// Status line shown below the simulation, cut to fit `columns` cells
+fn status_text(tick: u64, paused: bool, simulation: &dyn Simulation, columns: u16) -> String {
+    let status = format!(
+        " tick {tick} | {} | {} | P pause  Space step  R randomise  M mode  S save  L load  Esc quit",
+        if paused { "paused" } else { "running" },
+        simulation::status_line(simulation),
+    );
+    status.chars().take(columns as usize).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::SIMULATIONS;
+
+    #[test]
+    fn the_status_line_fits_the_terminal() {
+        let simulation = (SIMULATIONS[0].create)(64, 48);
+        let status = status_text(12, true, simulation.as_ref(), 200);
+        assert!(status.starts_with(" tick 12 | paused | tiles"));
+        assert!(status.ends_with("Esc quit"));
+        assert_eq!(status_text(12, false, simulation.as_ref(), 14), " tick 12 | run");
+    }
+}